@@ -0,0 +1,281 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, CreateAccount, Transfer};
+use anchor_spl::associated_token::{self, AssociatedToken};
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    state::Mint as MintState,
+};
+use anchor_spl::token_2022::{self, InitializeMint2, Token2022};
+use anchor_spl::token_2022_extensions::spl_pod::optional_keys::OptionalNonZeroPubkey;
+use anchor_spl::token_2022_extensions::spl_token_metadata_interface::state::{
+    Field, TokenMetadata,
+};
+use anchor_spl::token_2022_extensions::{
+    metadata_pointer_initialize, non_transferable_mint_initialize, token_metadata_initialize,
+    token_metadata_update_field, MetadataPointerInitialize, NonTransferableMintInitialize,
+    TokenMetadataInitialize, TokenMetadataUpdateField,
+};
+
+use crate::{has_current_layout, AtomConfig, AtomId, ErrorCode, RankTier};
+
+pub const BADGE_NAME: &str = "AtomID";
+pub const BADGE_SYMBOL: &str = "ATOMID";
+
+const BADGE_EXTENSIONS: [ExtensionType; 2] =
+    [ExtensionType::NonTransferable, ExtensionType::MetadataPointer];
+
+fn badge_metadata(
+    mint: Pubkey,
+    authority: Pubkey,
+    uri: String,
+    rank: u8,
+    total_burned: u64,
+) -> TokenMetadata {
+    TokenMetadata {
+        update_authority: OptionalNonZeroPubkey(authority),
+        mint,
+        name: BADGE_NAME.to_string(),
+        symbol: BADGE_SYMBOL.to_string(),
+        uri,
+        additional_metadata: vec![
+            ("rank".to_string(), rank.to_string()),
+            ("total_burned".to_string(), total_burned.to_string()),
+        ],
+    }
+}
+
+/// Rent-exempt balance for a badge mint carrying `metadata`. Token-2022
+/// reallocates the mint as metadata is written but doesn't fund it.
+fn badge_mint_rent(metadata: &TokenMetadata) -> Result<(usize, u64)> {
+    let mint_len = ExtensionType::try_calculate_account_len::<MintState>(&BADGE_EXTENSIONS)?;
+    let metadata_len = metadata.tlv_size_of()?;
+    let lamports = Rent::get()?.minimum_balance(mint_len + metadata_len);
+    Ok((mint_len, lamports))
+}
+
+/// Creates the non-transferable badge mint with embedded metadata. The mint
+/// and `authority` are PDAs of this program, signed for by `mint_seeds` and
+/// `authority_seeds`.
+#[allow(clippy::too_many_arguments)]
+pub fn create_badge_mint<'info>(
+    payer: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    mint_seeds: &[&[u8]],
+    authority_seeds: &[&[u8]],
+    uri: String,
+    rank: u8,
+    total_burned: u64,
+) -> Result<()> {
+    let metadata = badge_metadata(mint.key(), authority.key(), uri, rank, total_burned);
+    let (mint_len, lamports) = badge_mint_rent(&metadata)?;
+
+    system_program::create_account(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            CreateAccount {
+                from: payer.clone(),
+                to: mint.clone(),
+            },
+            &[mint_seeds],
+        ),
+        lamports,
+        mint_len as u64,
+        &token_2022::ID,
+    )?;
+
+    non_transferable_mint_initialize(CpiContext::new(
+        token_program.clone(),
+        NonTransferableMintInitialize {
+            token_program_id: token_program.clone(),
+            mint: mint.clone(),
+        },
+    ))?;
+
+    metadata_pointer_initialize(
+        CpiContext::new(
+            token_program.clone(),
+            MetadataPointerInitialize {
+                token_program_id: token_program.clone(),
+                mint: mint.clone(),
+            },
+        ),
+        Some(authority.key()),
+        Some(mint.key()),
+    )?;
+
+    token_2022::initialize_mint2(
+        CpiContext::new(
+            token_program.clone(),
+            InitializeMint2 { mint: mint.clone() },
+        ),
+        0,
+        &authority.key(),
+        Some(&authority.key()),
+    )?;
+
+    token_metadata_initialize(
+        CpiContext::new_with_signer(
+            token_program.clone(),
+            TokenMetadataInitialize {
+                program_id: token_program.clone(),
+                mint: mint.clone(),
+                metadata: mint.clone(),
+                mint_authority: authority.clone(),
+                update_authority: authority.clone(),
+            },
+            &[authority_seeds],
+        ),
+        metadata.name,
+        metadata.symbol,
+        metadata.uri,
+    )?;
+
+    for (key, value) in metadata.additional_metadata {
+        token_metadata_update_field(
+            CpiContext::new_with_signer(
+                token_program.clone(),
+                TokenMetadataUpdateField {
+                    program_id: token_program.clone(),
+                    metadata: mint.clone(),
+                    update_authority: authority.clone(),
+                },
+                &[authority_seeds],
+            ),
+            Field::Key(key),
+            value,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Rewrites the rank and total burned fields of an existing badge, and its
+/// URI if `uri` is given, topping up the mint's rent from `payer` if the
+/// metadata grew.
+#[allow(clippy::too_many_arguments)]
+pub fn update_badge_fields<'info>(
+    payer: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    authority_seeds: &[&[u8]],
+    uri: Option<String>,
+    rank: u8,
+    total_burned: u64,
+) -> Result<()> {
+    let current_uri = {
+        let data = mint.try_borrow_data()?;
+        let state = StateWithExtensions::<MintState>::unpack(&data)?;
+        state.get_variable_len_extension::<TokenMetadata>()?.uri
+    };
+    let uri_changed = uri.as_ref().is_some_and(|uri| *uri != current_uri);
+    let metadata = badge_metadata(
+        mint.key(),
+        authority.key(),
+        uri.unwrap_or(current_uri),
+        rank,
+        total_burned,
+    );
+    let (_, lamports) = badge_mint_rent(&metadata)?;
+    let shortfall = lamports.saturating_sub(mint.lamports());
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                Transfer {
+                    from: payer.clone(),
+                    to: mint.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+
+    let uri_field = uri_changed.then_some((Field::Uri, metadata.uri));
+    let fields = uri_field.into_iter().chain(
+        metadata
+            .additional_metadata
+            .into_iter()
+            .map(|(key, value)| (Field::Key(key), value)),
+    );
+
+    for (field, value) in fields {
+        token_metadata_update_field(
+            CpiContext::new_with_signer(
+                token_program.clone(),
+                TokenMetadataUpdateField {
+                    program_id: token_program.clone(),
+                    metadata: mint.clone(),
+                    update_authority: authority.clone(),
+                },
+                &[authority_seeds],
+            ),
+            field,
+            value,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MintBadge<'info> {
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = atom_id.owner == user.key() @ ErrorCode::Unauthorized,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: Badge mint PDA - created and initialized in the handler
+    #[account(
+        mut,
+        seeds = [b"badge_mint", atom_id.key().as_ref()],
+        bump
+    )]
+    pub badge_mint: UncheckedAccount<'info>,
+
+    /// CHECK: Badge mint and metadata authority PDA
+    #[account(
+        seeds = [b"badge_authority"],
+        bump
+    )]
+    pub badge_authority: UncheckedAccount<'info>,
+
+    /// CHECK: User's associated token account for the badge - created via CPI
+    #[account(
+        mut,
+        address = associated_token::get_associated_token_address_with_program_id(
+            &user.key(),
+            &badge_mint.key(),
+            &token_2022::ID,
+        )
+    )]
+    pub user_badge_account: UncheckedAccount<'info>,
+
+    /// Tier for the current rank, whose badge URI is used if provided
+    #[account(
+        seeds = [b"rank_tier", rank_tier.rank.to_le_bytes().as_ref()],
+        bump = rank_tier.bump
+    )]
+    pub rank_tier: Option<Account<'info, RankTier>>,
+
+    pub token_2022_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
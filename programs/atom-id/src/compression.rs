@@ -0,0 +1,573 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+
+use anchor_spl::token::Token;
+
+use crate::{
+    AtomConfig, AtomId, CompressedAtomIdOwner, ErrorCode, IdentityTree, SAS_PROGRAM_ID,
+};
+
+pub const MAX_TREE_DEPTH: usize = 20;
+/// Number of recent changes kept so proofs built against a slightly stale
+/// root can still be applied.
+pub const CHANGELOG_LEN: usize = 8;
+
+pub const EMPTY_LEAF: [u8; 32] = [0; 32];
+
+/// Identity data stored off-chain and committed to as a tree leaf.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CompressedAtomId {
+    pub owner: Pubkey,
+    pub total_burned: u64,
+    pub rank: u8,
+    pub created_at_slot: u64,
+    pub updated_at_slot: u64,
+    pub burn_count: u64,
+}
+
+impl CompressedAtomId {
+    pub fn leaf_hash(&self) -> [u8; 32] {
+        hashv(&[b"atomid_leaf", &self.try_to_vec().unwrap()]).to_bytes()
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, InitSpace)]
+pub struct ChangeLogEntry {
+    pub root: [u8; 32],
+    /// Nodes on the changed leaf's path, from the leaf (height 0) upwards
+    #[max_len(MAX_TREE_DEPTH)]
+    pub path: Vec<[u8; 32]>,
+    pub index: u32,
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hashv(&[left, right]).to_bytes()
+}
+
+/// Roots of empty subtrees, by height.
+pub fn zero_hashes(depth: usize) -> Vec<[u8; 32]> {
+    let mut zeros = Vec::with_capacity(depth);
+    let mut node = EMPTY_LEAF;
+    for _ in 0..depth {
+        zeros.push(node);
+        node = hash_pair(&node, &node);
+    }
+    zeros
+}
+
+/// Path nodes (leaf first) and root obtained by placing `leaf` at `index`
+/// with sibling hashes `proof`.
+pub fn compute_path(leaf: [u8; 32], index: u32, proof: &[[u8; 32]]) -> (Vec<[u8; 32]>, [u8; 32]) {
+    let mut path = Vec::with_capacity(proof.len());
+    let mut node = leaf;
+    for (height, sibling) in proof.iter().enumerate() {
+        path.push(node);
+        node = if (index >> height) & 1 == 0 {
+            hash_pair(&node, sibling)
+        } else {
+            hash_pair(sibling, &node)
+        };
+    }
+    (path, node)
+}
+
+impl IdentityTree {
+    pub fn root(&self) -> [u8; 32] {
+        self.changelog[self.active_index as usize].root
+    }
+
+    pub fn initialize(&mut self, depth: u8) -> Result<()> {
+        require!(
+            depth > 0 && depth as usize <= MAX_TREE_DEPTH,
+            ErrorCode::InvalidTreeDepth
+        );
+
+        let zeros = zero_hashes(depth as usize);
+        let (path, root) = compute_path(EMPTY_LEAF, 0, &zeros);
+
+        self.depth = depth;
+        self.next_index = 0;
+        self.sequence = 0;
+        self.filled_subtrees = zeros;
+        self.changelog = vec![ChangeLogEntry::default(); CHANGELOG_LEN];
+        self.changelog[0] = ChangeLogEntry {
+            root,
+            path,
+            index: 0,
+        };
+        self.active_index = 0;
+        Ok(())
+    }
+
+    fn push_change(&mut self, root: [u8; 32], path: Vec<[u8; 32]>, index: u32) {
+        self.active_index = ((self.active_index as usize + 1) % CHANGELOG_LEN) as u8;
+        self.changelog[self.active_index as usize] = ChangeLogEntry { root, path, index };
+        self.sequence += 1;
+    }
+
+    /// Appends `leaf` without a proof, returning its index.
+    pub fn append(&mut self, leaf: [u8; 32]) -> Result<u32> {
+        let index = self.next_index;
+        require!(
+            (index as u64) < (1u64 << self.depth),
+            ErrorCode::IdentityTreeFull
+        );
+
+        let zeros = zero_hashes(self.depth as usize);
+        let mut path = Vec::with_capacity(self.depth as usize);
+        let mut node = leaf;
+        for (height, (filled, zero)) in self.filled_subtrees.iter_mut().zip(&zeros).enumerate() {
+            path.push(node);
+            node = if (index >> height) & 1 == 0 {
+                *filled = node;
+                hash_pair(&node, zero)
+            } else {
+                hash_pair(filled, &node)
+            };
+        }
+
+        self.next_index += 1;
+        self.push_change(node, path, index);
+        Ok(index)
+    }
+
+    /// Replaces `old_leaf` at `index` with `new_leaf`. `proof` may have been
+    /// built against any root still in the changelog; it is fast-forwarded
+    /// over later changes to other leaves.
+    pub fn replace(
+        &mut self,
+        root: [u8; 32],
+        old_leaf: [u8; 32],
+        new_leaf: [u8; 32],
+        index: u32,
+        proof: &[[u8; 32]],
+    ) -> Result<()> {
+        require!(index < self.next_index, ErrorCode::InvalidMerkleProof);
+        require!(
+            proof.len() == self.depth as usize,
+            ErrorCode::InvalidMerkleProof
+        );
+
+        let slot_back = |back: usize| {
+            (self.active_index as usize + CHANGELOG_LEN - back) % CHANGELOG_LEN
+        };
+        let changes_since = (0..CHANGELOG_LEN.min(self.sequence as usize + 1))
+            .find(|&back| self.changelog[slot_back(back)].root == root)
+            .ok_or(ErrorCode::StaleMerkleRoot)?;
+
+        let mut proof = proof.to_vec();
+        for back in (0..changes_since).rev() {
+            let change = &self.changelog[slot_back(back)];
+            require!(change.index != index, ErrorCode::StaleMerkleRoot);
+
+            // The changed leaf's path meets ours at the highest differing bit;
+            // the node just below that point is our new sibling
+            let height = 31 - (change.index ^ index).leading_zeros() as usize;
+            proof[height] = change.path[height];
+        }
+
+        let (_, current_root) = compute_path(old_leaf, index, &proof);
+        require!(current_root == self.root(), ErrorCode::InvalidMerkleProof);
+
+        let (path, new_root) = compute_path(new_leaf, index, &proof);
+
+        // Keep the append frontier in sync when the replaced leaf sits under
+        // the left sibling the next append will hash against
+        for (height, node) in path.iter().enumerate() {
+            let next = self.next_index >> height;
+            if next & 1 == 1 && index >> height == next - 1 {
+                self.filled_subtrees[height] = *node;
+            }
+        }
+
+        self.push_change(new_root, path, index);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeIdentityTree<'info> {
+    #[account(
+        init,
+        payer = config_manager,
+        space = 8 + IdentityTree::INIT_SPACE,
+        seeds = [b"identity_tree"],
+        bump
+    )]
+    pub identity_tree: Account<'info, IdentityTree>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.config_manager == config_manager.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub config_manager: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateCompressedAtomId<'info> {
+    #[account(
+        mut,
+        seeds = [b"identity_tree"],
+        bump = identity_tree.bump
+    )]
+    pub identity_tree: Account<'info, IdentityTree>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// One leaf per wallet: creation fails if the marker already exists
+    #[account(
+        init,
+        payer = user,
+        space = 8 + CompressedAtomIdOwner::INIT_SPACE,
+        seeds = [b"compressed_atomid", user.key().as_ref()],
+        bump
+    )]
+    pub compressed_atomid: Account<'info, CompressedAtomIdOwner>,
+
+    /// CHECK: User's full AtomID PDA, must not exist
+    #[account(
+        seeds = [b"atomid", user.key().as_ref()],
+        bump
+    )]
+    pub atom_id: UncheckedAccount<'info>,
+
+    /// CHECK: User's wallet link PDA, must not exist
+    #[account(
+        seeds = [b"wallet_link", user.key().as_ref()],
+        bump
+    )]
+    pub wallet_link: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the token program during burn
+    #[account(mut)]
+    pub user_token_account: AccountInfo<'info>,
+
+    /// CHECK: Validated in instruction handler
+    #[account(mut)]
+    pub atom_mint: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpgradeCompressedAtomId<'info> {
+    #[account(
+        mut,
+        seeds = [b"identity_tree"],
+        bump = identity_tree.bump
+    )]
+    pub identity_tree: Account<'info, IdentityTree>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    pub user: Signer<'info>,
+
+    /// CHECK: Validated by the token program during burn
+    #[account(mut)]
+    pub user_token_account: AccountInfo<'info>,
+
+    /// CHECK: Validated in instruction handler
+    #[account(mut)]
+    pub atom_mint: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct PromoteCompressedAtomId<'info> {
+    #[account(
+        mut,
+        seeds = [b"identity_tree"],
+        bump = identity_tree.bump
+    )]
+    pub identity_tree: Account<'info, IdentityTree>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + AtomId::INIT_SPACE,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"compressed_atomid", user.key().as_ref()],
+        bump = compressed_atomid.bump
+    )]
+    pub compressed_atomid: Account<'info, CompressedAtomIdOwner>,
+
+    /// CHECK: User's wallet link PDA, must not exist
+    #[account(
+        seeds = [b"wallet_link", user.key().as_ref()],
+        bump
+    )]
+    pub wallet_link: UncheckedAccount<'info>,
+
+    /// CHECK: Linked attestation PDA naming this wallet as recipient, must
+    /// not exist
+    #[account(
+        seeds = [b"linked_attestation", user.key().as_ref()],
+        bump
+    )]
+    pub linked_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: SAS attestation PDA - will be created by SAS program via CPI
+    #[account(mut)]
+    pub sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS schema account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_schema: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Rank hook registry PDA, required once hooks are registered
+    #[account(
+        seeds = [b"rank_hooks"],
+        bump
+    )]
+    pub rank_hooks: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Signer PDA hooks are called with, required when hooks are registered
+    #[account(
+        seeds = [b"hook_authority"],
+        bump
+    )]
+    pub hook_authority: Option<UncheckedAccount<'info>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEPTH: u8 = 4;
+
+    fn tree() -> IdentityTree {
+        let mut tree = IdentityTree {
+            depth: 0,
+            next_index: 0,
+            sequence: 0,
+            filled_subtrees: Vec::new(),
+            changelog: Vec::new(),
+            active_index: 0,
+            bump: 0,
+        };
+        tree.initialize(DEPTH).unwrap();
+        tree
+    }
+
+    fn leaf(n: u8) -> [u8; 32] {
+        hashv(&[b"leaf", &[n]]).to_bytes()
+    }
+
+    /// Every level of the full tree, leaves first.
+    fn levels(leaves: &[[u8; 32]]) -> Vec<Vec<[u8; 32]>> {
+        let mut level = leaves.to_vec();
+        level.resize(1 << DEPTH, EMPTY_LEAF);
+        let mut levels = vec![level];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hash_pair(&pair[0], &pair[1]))
+                .collect();
+            levels.push(next);
+        }
+        levels
+    }
+
+    fn naive_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+        levels(leaves).last().unwrap()[0]
+    }
+
+    fn naive_proof(leaves: &[[u8; 32]], index: u32) -> Vec<[u8; 32]> {
+        levels(leaves)[..DEPTH as usize]
+            .iter()
+            .enumerate()
+            .map(|(height, level)| level[((index >> height) ^ 1) as usize])
+            .collect()
+    }
+
+    #[test]
+    fn append_matches_full_tree() {
+        let mut tree = tree();
+        let mut leaves = Vec::new();
+        assert_eq!(tree.root(), naive_root(&leaves));
+
+        for n in 0..1u8 << DEPTH {
+            assert_eq!(tree.append(leaf(n)).unwrap(), n as u32);
+            leaves.push(leaf(n));
+            assert_eq!(tree.root(), naive_root(&leaves));
+        }
+
+        assert_eq!(tree.append(leaf(0)).unwrap_err(), ErrorCode::IdentityTreeFull.into());
+    }
+
+    #[test]
+    fn replace_with_current_proof() {
+        let mut tree = tree();
+        let mut leaves: Vec<_> = (0..5).map(leaf).collect();
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+
+        for index in [4, 0, 2] {
+            let root = tree.root();
+            let proof = naive_proof(&leaves, index);
+            let new_leaf = leaf(100 + index as u8);
+            tree.replace(root, leaves[index as usize], new_leaf, index, &proof)
+                .unwrap();
+            leaves[index as usize] = new_leaf;
+            assert_eq!(tree.root(), naive_root(&leaves));
+        }
+
+        // Replacing the last leaf must keep later appends consistent
+        tree.append(leaf(5)).unwrap();
+        leaves.push(leaf(5));
+        assert_eq!(tree.root(), naive_root(&leaves));
+    }
+
+    #[test]
+    fn replace_rejects_wrong_leaf_or_proof() {
+        let mut tree = tree();
+        let leaves: Vec<_> = (0..3).map(leaf).collect();
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+        let root = tree.root();
+        let proof = naive_proof(&leaves, 1);
+
+        assert_eq!(
+            tree.replace(root, leaf(9), leaf(10), 1, &proof).unwrap_err(),
+            ErrorCode::InvalidMerkleProof.into()
+        );
+        assert_eq!(
+            tree.replace(root, leaves[1], leaf(10), 2, &proof).unwrap_err(),
+            ErrorCode::InvalidMerkleProof.into()
+        );
+        assert_eq!(
+            tree.replace(root, leaves[1], leaf(10), 3, &proof).unwrap_err(),
+            ErrorCode::InvalidMerkleProof.into()
+        );
+        assert_eq!(
+            tree.replace([7; 32], leaves[1], leaf(10), 1, &proof).unwrap_err(),
+            ErrorCode::StaleMerkleRoot.into()
+        );
+        assert_eq!(tree.root(), root);
+    }
+
+    #[test]
+    fn stale_proof_is_fast_forwarded() {
+        let mut tree = tree();
+        let mut leaves: Vec<_> = (0..6).map(leaf).collect();
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+
+        let stale_root = tree.root();
+        let stale_proof = naive_proof(&leaves, 1);
+
+        // Changes to other leaves, in both halves of the tree, after the
+        // proof was taken
+        for (index, n) in [(0u32, 20u8), (5, 21), (3, 22)] {
+            let proof = naive_proof(&leaves, index);
+            tree.replace(tree.root(), leaves[index as usize], leaf(n), index, &proof)
+                .unwrap();
+            leaves[index as usize] = leaf(n);
+        }
+        tree.append(leaf(23)).unwrap();
+        leaves.push(leaf(23));
+
+        tree.replace(stale_root, leaves[1], leaf(24), 1, &stale_proof)
+            .unwrap();
+        leaves[1] = leaf(24);
+        assert_eq!(tree.root(), naive_root(&leaves));
+    }
+
+    #[test]
+    fn stale_proof_for_changed_leaf_is_rejected() {
+        let mut tree = tree();
+        let mut leaves: Vec<_> = (0..4).map(leaf).collect();
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+
+        let stale_root = tree.root();
+        let stale_proof = naive_proof(&leaves, 2);
+        tree.replace(stale_root, leaves[2], leaf(30), 2, &stale_proof)
+            .unwrap();
+        leaves[2] = leaf(30);
+
+        assert_eq!(
+            tree.replace(stale_root, leaf(2), leaf(31), 2, &stale_proof)
+                .unwrap_err(),
+            ErrorCode::StaleMerkleRoot.into()
+        );
+    }
+
+    #[test]
+    fn proof_older_than_changelog_is_rejected() {
+        let mut tree = tree();
+        let mut leaves = vec![leaf(0)];
+        tree.append(leaves[0]).unwrap();
+
+        let stale_root = tree.root();
+        let stale_proof = naive_proof(&leaves, 0);
+        for n in 1..=CHANGELOG_LEN as u8 {
+            tree.append(leaf(n)).unwrap();
+            leaves.push(leaf(n));
+        }
+
+        assert_eq!(
+            tree.replace(stale_root, leaves[0], leaf(40), 0, &stale_proof)
+                .unwrap_err(),
+            ErrorCode::StaleMerkleRoot.into()
+        );
+    }
+}
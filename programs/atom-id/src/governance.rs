@@ -0,0 +1,331 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    has_current_layout, AtomConfig, AtomId, ErrorCode, MaxVoterWeightRecord, RealmVoter,
+    VoterWeightRecord,
+};
+
+/// `GovernanceAccountType::RealmV2` in spl-governance.
+const REALM_V2_ACCOUNT_TYPE: u8 = 16;
+
+/// Voter weight as `base + rank * per_rank + total_burned / burn_divisor`.
+/// A `burn_divisor` of 0 leaves burns out of the weight.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub struct VoterWeightFormula {
+    pub base: u64,
+    pub per_rank: u64,
+    pub burn_divisor: u64,
+}
+
+impl VoterWeightFormula {
+    pub fn weight(&self, atom_id: &AtomId) -> Result<u64> {
+        let burned = match self.burn_divisor {
+            0 => 0,
+            divisor => atom_id.total_burned / divisor,
+        };
+        self.per_rank
+            .checked_mul(atom_id.rank as u64)
+            .and_then(|w| w.checked_add(self.base))
+            .and_then(|w| w.checked_add(burned))
+            .ok_or(ErrorCode::Overflow.into())
+    }
+}
+
+/// Action a voter weight was computed for, as defined by the
+/// spl-governance addin interface.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum VoterWeightAction {
+    CastVote,
+    CommentProposal,
+    CreateGovernance,
+    CreateProposal,
+    SignOffProposal,
+}
+
+/// Reads the authority of an spl-governance `RealmV2` account owned by
+/// `governance_program`.
+pub fn realm_authority(realm: &AccountInfo, governance_program: &Pubkey) -> Result<Option<Pubkey>> {
+    require_keys_eq!(*realm.owner, *governance_program, ErrorCode::InvalidRealm);
+    let data = realm.try_borrow_data()?;
+    require!(
+        data.first() == Some(&REALM_V2_ACCOUNT_TYPE),
+        ErrorCode::InvalidRealm
+    );
+
+    // account_type, community_mint, then RealmConfig: two legacy bytes,
+    // 6 reserved, min_community_weight_to_create_governance (u64) and
+    // community_mint_max_voter_weight_source (tag + u64)
+    let mut offset = 1 + 32 + 2 + 6 + 8 + 9;
+
+    // council_mint: Option<Pubkey>
+    offset += match data.get(offset) {
+        Some(0) => 1,
+        Some(1) => 33,
+        _ => return err!(ErrorCode::InvalidRealm),
+    };
+
+    // reserved [u8; 6], legacy1 u16
+    offset += 6 + 2;
+
+    match data.get(offset) {
+        Some(0) => Ok(None),
+        Some(1) => {
+            let bytes = data
+                .get(offset + 1..offset + 33)
+                .ok_or(ErrorCode::InvalidRealm)?;
+            Ok(Some(Pubkey::try_from(bytes).unwrap()))
+        }
+        _ => err!(ErrorCode::InvalidRealm),
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(governance_program: Pubkey, governing_token_mint: Pubkey)]
+pub struct ConfigureRealmVoter<'info> {
+    #[account(
+        init,
+        payer = realm_authority,
+        space = 8 + RealmVoter::INIT_SPACE,
+        seeds = [b"realm_voter", realm.key().as_ref(), governing_token_mint.as_ref()],
+        bump
+    )]
+    pub realm_voter: Account<'info, RealmVoter>,
+
+    /// CHECK: spl-governance realm, parsed in instruction handler
+    pub realm: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub realm_authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRealmVoter<'info> {
+    #[account(
+        mut,
+        seeds = [b"realm_voter", realm.key().as_ref(), realm_voter.governing_token_mint.as_ref()],
+        bump = realm_voter.bump
+    )]
+    pub realm_voter: Account<'info, RealmVoter>,
+
+    /// CHECK: spl-governance realm, parsed in instruction handler
+    #[account(address = realm_voter.realm)]
+    pub realm: UncheckedAccount<'info>,
+
+    pub realm_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateVoterWeightRecord<'info> {
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + VoterWeightRecord::INIT_SPACE,
+        seeds = [
+            b"voter_weight_record",
+            realm_voter.realm.as_ref(),
+            realm_voter.governing_token_mint.as_ref(),
+            user.key().as_ref()
+        ],
+        bump
+    )]
+    pub voter_weight_record: Account<'info, VoterWeightRecord>,
+
+    #[account(
+        seeds = [b"realm_voter", realm_voter.realm.as_ref(), realm_voter.governing_token_mint.as_ref()],
+        bump = realm_voter.bump
+    )]
+    pub realm_voter: Account<'info, RealmVoter>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVoterWeightRecord<'info> {
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"voter_weight_record",
+            realm_voter.realm.as_ref(),
+            realm_voter.governing_token_mint.as_ref(),
+            user.key().as_ref()
+        ],
+        bump
+    )]
+    pub voter_weight_record: Account<'info, VoterWeightRecord>,
+
+    #[account(
+        seeds = [b"realm_voter", realm_voter.realm.as_ref(), realm_voter.governing_token_mint.as_ref()],
+        bump = realm_voter.bump
+    )]
+    pub realm_voter: Account<'info, RealmVoter>,
+
+    #[account(
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = atom_id.owner == user.key() @ ErrorCode::Unauthorized,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateMaxVoterWeightRecord<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + MaxVoterWeightRecord::INIT_SPACE,
+        seeds = [
+            b"max_voter_weight_record",
+            realm_voter.realm.as_ref(),
+            realm_voter.governing_token_mint.as_ref()
+        ],
+        bump
+    )]
+    pub max_voter_weight_record: Account<'info, MaxVoterWeightRecord>,
+
+    #[account(
+        seeds = [b"realm_voter", realm_voter.realm.as_ref(), realm_voter.governing_token_mint.as_ref()],
+        bump = realm_voter.bump
+    )]
+    pub realm_voter: Account<'info, RealmVoter>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMaxVoterWeightRecord<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"max_voter_weight_record",
+            realm_voter.realm.as_ref(),
+            realm_voter.governing_token_mint.as_ref()
+        ],
+        bump
+    )]
+    pub max_voter_weight_record: Account<'info, MaxVoterWeightRecord>,
+
+    #[account(
+        seeds = [b"realm_voter", realm_voter.realm.as_ref(), realm_voter.governing_token_mint.as_ref()],
+        bump = realm_voter.bump
+    )]
+    pub realm_voter: Account<'info, RealmVoter>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom_id(rank: u8, total_burned: u64) -> AtomId {
+        let mut atom_id = AtomId::deserialize(&mut &[0; AtomId::INIT_SPACE][..]).unwrap();
+        atom_id.rank = rank;
+        atom_id.total_burned = total_burned;
+        atom_id
+    }
+
+    #[test]
+    fn weight_formula() {
+        let formula = VoterWeightFormula {
+            base: 10,
+            per_rank: 100,
+            burn_divisor: 1_000,
+        };
+        assert_eq!(formula.weight(&atom_id(0, 0)).unwrap(), 10);
+        assert_eq!(formula.weight(&atom_id(3, 5_999)).unwrap(), 10 + 300 + 5);
+
+        let ranks_only = VoterWeightFormula {
+            burn_divisor: 0,
+            ..formula
+        };
+        assert_eq!(ranks_only.weight(&atom_id(3, u64::MAX)).unwrap(), 310);
+
+        let huge = VoterWeightFormula {
+            per_rank: u64::MAX,
+            ..formula
+        };
+        assert_eq!(
+            huge.weight(&atom_id(2, 0)).unwrap_err(),
+            ErrorCode::Overflow.into()
+        );
+    }
+
+    /// `RealmV2` data up to and including `authority`.
+    fn realm_data(council_mint: Option<Pubkey>, authority: Option<Pubkey>) -> Vec<u8> {
+        let mut data = vec![REALM_V2_ACCOUNT_TYPE];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(&[0; 2 + 6 + 8 + 9]);
+        data.extend(council_mint.map_or(vec![0], |mint| [&[1], mint.as_ref()].concat()));
+        data.extend_from_slice(&[0; 6 + 2]);
+        data.extend(authority.map_or(vec![0], |key| [&[1], key.as_ref()].concat()));
+        data
+    }
+
+    fn parse(mut data: Vec<u8>, owner: &Pubkey, program: &Pubkey) -> Result<Option<Pubkey>> {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let realm = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            owner,
+            false,
+            0,
+        );
+        realm_authority(&realm, program)
+    }
+
+    #[test]
+    fn realm_authority_parsing() {
+        let program = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+
+        for council_mint in [None, Some(Pubkey::new_unique())] {
+            let data = realm_data(council_mint, Some(authority));
+            assert_eq!(parse(data, &program, &program).unwrap(), Some(authority));
+            let data = realm_data(council_mint, None);
+            assert_eq!(parse(data, &program, &program).unwrap(), None);
+        }
+
+        let invalid = |result: Result<Option<Pubkey>>| {
+            assert_eq!(result.unwrap_err(), ErrorCode::InvalidRealm.into())
+        };
+        let data = realm_data(None, Some(authority));
+        invalid(parse(data.clone(), &Pubkey::new_unique(), &program));
+
+        let mut wrong_type = data.clone();
+        wrong_type[0] = REALM_V2_ACCOUNT_TYPE + 1;
+        invalid(parse(wrong_type, &program, &program));
+
+        invalid(parse(data[..data.len() - 1].to_vec(), &program, &program));
+
+        let mut bad_option = data;
+        bad_option[1 + 32 + 2 + 6 + 8 + 9] = 2;
+        invalid(parse(bad_option, &program, &program));
+    }
+}
@@ -0,0 +1,347 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    has_current_layout, normalize_handle, AtomConfig, AtomId, ErrorCode, Guild, GuildMember,
+    SAS_PROGRAM_ID,
+};
+
+/// Guild names follow the handle rules and must already be normalized, since
+/// they seed the guild PDA.
+pub fn validate_guild_name(name: &str) -> Result<()> {
+    require!(
+        normalize_handle(name).ok().as_deref() == Some(name),
+        ErrorCode::InvalidGuildName
+    );
+    Ok(())
+}
+
+impl Guild {
+    /// Adds a member's burn to the pooled total and re-ranks the guild.
+    /// Burns stay in the pool when the member later leaves.
+    pub fn record_contribution(&mut self, config: &AtomConfig, burn_amount: u64, slot: u64) -> Result<()> {
+        self.total_burned = self
+            .total_burned
+            .checked_add(burn_amount)
+            .ok_or(ErrorCode::Overflow)?;
+        self.burn_count = self
+            .burn_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+        self.rank = config.guild_rank_for(self.total_burned);
+        self.updated_at_slot = slot;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeGuildSasSchema<'info> {
+    #[account(
+        mut,
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.sas_manager == sas_manager.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub sas_manager: Signer<'info>,
+
+    /// CHECK: PDA used to sign SAS operations
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: UncheckedAccount<'info>,
+
+    /// CHECK: AtomID credential from config
+    #[account(
+        owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram,
+        address = atom_config.sas_credential @ ErrorCode::InvalidSasCredential
+    )]
+    pub sas_credential: UncheckedAccount<'info>,
+
+    /// CHECK: Created by SAS program
+    #[account(mut)]
+    pub sas_schema: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: SAS program for CPI, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(name: String)]
+pub struct CreateGuild<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Guild::INIT_SPACE,
+        seeds = [b"guild", name.as_bytes()],
+        bump
+    )]
+    pub guild: Account<'info, Guild>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + GuildMember::INIT_SPACE,
+        seeds = [b"guild_member", guild.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub guild_member: Account<'info, GuildMember>,
+
+    #[account(
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddGuildMember<'info> {
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"guild", guild.name.as_bytes()],
+        bump = guild.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub guild: Account<'info, Guild>,
+
+    #[account(
+        init,
+        payer = member,
+        space = 8 + GuildMember::INIT_SPACE,
+        seeds = [b"guild_member", guild.key().as_ref(), member.key().as_ref()],
+        bump
+    )]
+    pub guild_member: Account<'info, GuildMember>,
+
+    #[account(
+        seeds = [b"atomid", member.key().as_ref()],
+        bump = member_atom_id.bump,
+        constraint = has_current_layout(&member_atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub member_atom_id: Account<'info, AtomId>,
+
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    pub member: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveGuildMember<'info> {
+    #[account(
+        mut,
+        seeds = [b"guild", guild.name.as_bytes()],
+        bump = guild.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub guild: Account<'info, Guild>,
+
+    #[account(
+        mut,
+        close = member,
+        seeds = [b"guild_member", guild.key().as_ref(), member.key().as_ref()],
+        bump = guild_member.bump
+    )]
+    pub guild_member: Account<'info, GuildMember>,
+
+    pub admin: Signer<'info>,
+
+    /// CHECK: Removed member, receives the membership rent
+    #[account(mut)]
+    pub member: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LeaveGuild<'info> {
+    #[account(
+        mut,
+        seeds = [b"guild", guild.name.as_bytes()],
+        bump = guild.bump
+    )]
+    pub guild: Account<'info, Guild>,
+
+    #[account(
+        mut,
+        close = member,
+        seeds = [b"guild_member", guild.key().as_ref(), member.key().as_ref()],
+        bump = guild_member.bump
+    )]
+    pub guild_member: Account<'info, GuildMember>,
+
+    #[account(mut)]
+    pub member: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferGuildAdmin<'info> {
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"guild", guild.name.as_bytes()],
+        bump = guild.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub guild: Account<'info, Guild>,
+
+    #[account(
+        seeds = [b"guild_member", guild.key().as_ref(), new_admin.key().as_ref()],
+        bump = new_admin_member.bump
+    )]
+    pub new_admin_member: Account<'info, GuildMember>,
+
+    pub admin: Signer<'info>,
+
+    pub new_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshGuildAttestation<'info> {
+    #[account(
+        mut,
+        seeds = [b"guild", guild.name.as_bytes()],
+        bump = guild.bump
+    )]
+    pub guild: Account<'info, Guild>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: `guild.attestation_payer`, refunded the rent of the current
+    /// attestation; required while one exists
+    #[account(mut)]
+    pub previous_payer: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Current guild attestation, verified against its PDA
+    #[account(mut)]
+    pub old_sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: Reissued guild attestation, verified against its PDA
+    #[account(mut)]
+    pub new_sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: Credential the current guild attestation was issued under
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub guild_sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: Guild schema account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_schema: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS event authority PDA
+    #[account(
+        seeds = [b"__event_authority"],
+        bump,
+        seeds::program = SAS_PROGRAM_ID
+    )]
+    pub sas_event_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(rank_thresholds: Vec<u64>, guild_rank_thresholds: Vec<u64>) -> AtomConfig {
+        let mut config = AtomConfig::deserialize(&mut &[0; AtomConfig::INIT_SPACE][..]).unwrap();
+        config.rank_thresholds = rank_thresholds;
+        config.guild_rank_thresholds = guild_rank_thresholds;
+        config
+    }
+
+    fn guild() -> Guild {
+        Guild::deserialize(&mut &[0; Guild::INIT_SPACE][..]).unwrap()
+    }
+
+    #[test]
+    fn record_contribution_pools_burns() {
+        let config = config(vec![1_000], vec![500, 2_000]);
+        let mut guild = guild();
+
+        guild.record_contribution(&config, 600, 10).unwrap();
+        assert_eq!(guild.total_burned, 600);
+        assert_eq!(guild.burn_count, 1);
+        assert_eq!(guild.rank, 1);
+        assert_eq!(guild.updated_at_slot, 10);
+
+        guild.record_contribution(&config, 1_400, 20).unwrap();
+        assert_eq!(guild.total_burned, 2_000);
+        assert_eq!(guild.burn_count, 2);
+        assert_eq!(guild.rank, 2);
+        assert_eq!(guild.updated_at_slot, 20);
+    }
+
+    #[test]
+    fn record_contribution_falls_back_to_rank_curve() {
+        let config = config(vec![1_000], vec![]);
+        let mut guild = guild();
+
+        guild.record_contribution(&config, 999, 1).unwrap();
+        assert_eq!(guild.rank, 0);
+        guild.record_contribution(&config, 1, 2).unwrap();
+        assert_eq!(guild.rank, 1);
+    }
+
+    #[test]
+    fn record_contribution_rejects_overflow() {
+        let config = config(vec![], vec![]);
+        let mut guild = guild();
+        guild.total_burned = u64::MAX;
+
+        assert_eq!(
+            guild.record_contribution(&config, 1, 1).unwrap_err(),
+            ErrorCode::Overflow.into()
+        );
+    }
+}
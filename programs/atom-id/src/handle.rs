@@ -0,0 +1,163 @@
+use anchor_lang::prelude::*;
+
+use crate::{has_current_layout, AtomConfig, AtomId, ErrorCode, Handle};
+
+/// Also the seed length limit, so a handle always fits in one seed.
+pub const MAX_HANDLE_LEN: usize = 32;
+
+/// Canonical form of a handle: leading `@` dropped, ASCII lowercased, only
+/// `a-z`, `0-9` and `_`.
+pub fn normalize_handle(name: &str) -> Result<String> {
+    let name = name.strip_prefix('@').unwrap_or(name).to_ascii_lowercase();
+    require!(
+        !name.is_empty() && name.len() <= MAX_HANDLE_LEN,
+        ErrorCode::InvalidHandle
+    );
+    require!(
+        name.bytes()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'_'),
+        ErrorCode::InvalidHandle
+    );
+    Ok(name)
+}
+
+/// Handles used as PDA seeds must already be normalized, otherwise `@Foo`
+/// and `foo` would get different accounts.
+pub fn validate_handle_seed(name: &str) -> Result<()> {
+    require!(normalize_handle(name)? == name, ErrorCode::InvalidHandle);
+    Ok(())
+}
+
+/// Names at or below `short_handle_len` need `short_handle_min_rank`.
+pub fn check_handle_rank(config: &AtomConfig, name: &str, rank: u8) -> Result<()> {
+    if name.len() <= config.short_handle_len as usize {
+        require!(
+            rank >= config.short_handle_min_rank,
+            ErrorCode::RankTooLowForHandle
+        );
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(name: String)]
+pub struct ClaimHandle<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Handle::INIT_SPACE,
+        seeds = [b"handle", name.as_bytes()],
+        bump
+    )]
+    pub handle: Account<'info, Handle>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = atom_id.owner == user.key() @ ErrorCode::Unauthorized,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseHandle<'info> {
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"handle", handle.name.as_bytes()],
+        bump = handle.bump,
+        constraint = handle.owner == user.key() @ ErrorCode::Unauthorized
+    )]
+    pub handle: Account<'info, Handle>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferHandle<'info> {
+    #[account(
+        mut,
+        seeds = [b"handle", handle.name.as_bytes()],
+        bump = handle.bump,
+        constraint = handle.owner == user.key() @ ErrorCode::Unauthorized
+    )]
+    pub handle: Account<'info, Handle>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid", new_owner.key().as_ref()],
+        bump = new_atom_id.bump,
+        constraint = has_current_layout(&new_atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub new_atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    pub user: Signer<'info>,
+
+    pub new_owner: Signer<'info>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(short_handle_len: u8, short_handle_min_rank: u8) -> AtomConfig {
+        let mut config = AtomConfig::deserialize(&mut &[0; AtomConfig::INIT_SPACE][..]).unwrap();
+        config.short_handle_len = short_handle_len;
+        config.short_handle_min_rank = short_handle_min_rank;
+        config
+    }
+
+    #[test]
+    fn short_handles_need_min_rank() {
+        let config = config(3, 5);
+        assert_eq!(
+            check_handle_rank(&config, "abc", 4).unwrap_err(),
+            ErrorCode::RankTooLowForHandle.into()
+        );
+        assert!(check_handle_rank(&config, "abc", 5).is_ok());
+        assert!(check_handle_rank(&config, "ab", 9).is_ok());
+        assert!(check_handle_rank(&config, "abcd", 0).is_ok());
+    }
+
+    #[test]
+    fn zero_len_disables_restriction() {
+        let config = config(0, 5);
+        assert!(check_handle_rank(&config, "a", 0).is_ok());
+    }
+}
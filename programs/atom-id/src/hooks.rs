@@ -0,0 +1,182 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::compute_units::sol_remaining_compute_units;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+
+use crate::{AtomConfig, ErrorCode, RankHookRegistry, RankHookSkipped};
+
+pub const MAX_RANK_HOOKS: usize = 8;
+pub const MAX_HOOK_COMPUTE_UNITS: u32 = 200_000;
+
+/// Anchor-style discriminator of `on_rank_changed`, i.e.
+/// `sha256("global:on_rank_changed")[..8]`.
+pub fn on_rank_changed_discriminator() -> [u8; 8] {
+    let hash = anchor_lang::solana_program::hash::hash(b"global:on_rank_changed");
+    hash.to_bytes()[..8].try_into().unwrap()
+}
+
+/// What happens when there isn't enough compute left to call a hook. A hook
+/// program that isn't passed always fails the rank change, so callers can't
+/// opt out of notifying it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum HookFailureMode {
+    /// The rank change fails.
+    Required,
+    /// The hook is skipped (with a `RankHookSkipped` event) instead.
+    BestEffort,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct RankHook {
+    pub program_id: Pubkey,
+    /// Most compute units the hook may use; also what must remain before
+    /// it is called
+    pub compute_units: u32,
+    pub failure_mode: HookFailureMode,
+}
+
+/// Calls `on_rank_changed(owner, old_rank, new_rank)` on every registered
+/// hook found in `hook_programs`. Hooks receive the AtomID account and the
+/// `hook_authority` PDA as signer, so they can check the call came from here.
+///
+/// A hook that errors still aborts the whole transaction; Solana has no way
+/// to catch a failed CPI.
+#[allow(clippy::too_many_arguments)]
+pub fn dispatch_rank_changed<'info>(
+    registry: &RankHookRegistry,
+    hook_programs: &[AccountInfo<'info>],
+    atom_id: &AccountInfo<'info>,
+    hook_authority: &AccountInfo<'info>,
+    authority_seeds: &[&[u8]],
+    owner: Pubkey,
+    old_rank: u8,
+    new_rank: u8,
+) -> Result<()> {
+    let mut data = on_rank_changed_discriminator().to_vec();
+    data.extend_from_slice(owner.as_ref());
+    data.push(old_rank);
+    data.push(new_rank);
+
+    for hook in &registry.hooks {
+        let program = hook_programs
+            .iter()
+            .find(|account| account.key() == hook.program_id && account.executable);
+
+        let program = program.ok_or(ErrorCode::RankHookUnavailable)?;
+
+        if sol_remaining_compute_units() < hook.compute_units as u64 {
+            require!(
+                hook.failure_mode == HookFailureMode::BestEffort,
+                ErrorCode::RankHookUnavailable
+            );
+            emit!(RankHookSkipped {
+                program_id: hook.program_id,
+                owner,
+            });
+            continue;
+        }
+
+        let ix = Instruction {
+            program_id: hook.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(atom_id.key(), false),
+                AccountMeta::new_readonly(hook_authority.key(), true),
+            ],
+            data: data.clone(),
+        };
+
+        let before = sol_remaining_compute_units();
+        invoke_signed(
+            &ix,
+            &[atom_id.clone(), hook_authority.clone(), program.clone()],
+            &[authority_seeds],
+        )?;
+        require!(
+            before - sol_remaining_compute_units() <= hook.compute_units as u64,
+            ErrorCode::RankHookComputeExceeded
+        );
+    }
+
+    Ok(())
+}
+
+/// Dispatches a rank change from any instruction that sets a rank. The
+/// registry may be omitted only while no hooks are registered; the AtomID
+/// must already be written out, since hooks may read it.
+#[allow(clippy::too_many_arguments)]
+pub fn notify_rank_changed<'info>(
+    config: &AtomConfig,
+    rank_hooks: Option<&AccountInfo<'info>>,
+    hook_authority: Option<(&AccountInfo<'info>, u8)>,
+    hook_programs: &[AccountInfo<'info>],
+    atom_id: &AccountInfo<'info>,
+    owner: Pubkey,
+    old_rank: u8,
+    new_rank: u8,
+) -> Result<()> {
+    if old_rank == new_rank || config.rank_hook_count == 0 {
+        return Ok(());
+    }
+
+    let (Some(rank_hooks), Some((hook_authority, authority_bump))) = (rank_hooks, hook_authority)
+    else {
+        return err!(ErrorCode::RankHookUnavailable);
+    };
+    require_keys_eq!(*rank_hooks.owner, crate::ID, ErrorCode::InvalidRankHook);
+    let registry = RankHookRegistry::try_deserialize(&mut &rank_hooks.try_borrow_data()?[..])?;
+
+    dispatch_rank_changed(
+        &registry,
+        hook_programs,
+        atom_id,
+        hook_authority,
+        &[b"hook_authority", &[authority_bump]],
+        owner,
+        old_rank,
+        new_rank,
+    )
+}
+
+#[derive(Accounts)]
+pub struct InitializeRankHooks<'info> {
+    #[account(
+        init,
+        payer = config_manager,
+        space = 8 + RankHookRegistry::INIT_SPACE,
+        seeds = [b"rank_hooks"],
+        bump
+    )]
+    pub rank_hooks: Account<'info, RankHookRegistry>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.config_manager == config_manager.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub config_manager: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRankHooks<'info> {
+    #[account(
+        mut,
+        seeds = [b"rank_hooks"],
+        bump = rank_hooks.bump
+    )]
+    pub rank_hooks: Account<'info, RankHookRegistry>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.config_manager == config_manager.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    pub config_manager: Signer<'info>,
+}
@@ -0,0 +1,199 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Burn};
+
+use crate::{
+    create_attestation_instruction, metadata_hash, notify_rank_changed,
+    serialize_atomid_attestation_data, AtomConfig, AtomId, AtomIdCreated, ErrorCode,
+    IdentityKind, ReputationEvent, ATOM_ID_LAYOUT_VERSION, ATTESTATION_VALIDITY_SECONDS,
+};
+
+/// Accounts `create_atomid` and `create_organization_atomid` both create an
+/// identity with. `owner` is the identity's key and signs the burn; `payer`
+/// funds the attestation.
+pub struct NewIdentity<'a, 'info> {
+    pub config: &'a AtomConfig,
+    pub atom_id: &'a mut Account<'info, AtomId>,
+    pub atom_id_bump: u8,
+    pub owner: AccountInfo<'info>,
+    pub payer: AccountInfo<'info>,
+    pub wallet_link: AccountInfo<'info>,
+    pub compressed_atomid: AccountInfo<'info>,
+    pub linked_attestation: AccountInfo<'info>,
+    pub owner_token_account: AccountInfo<'info>,
+    pub atom_mint: AccountInfo<'info>,
+    pub sas_attestation: AccountInfo<'info>,
+    pub sas_credential: AccountInfo<'info>,
+    pub sas_schema: AccountInfo<'info>,
+    pub sas_authority: AccountInfo<'info>,
+    pub sas_authority_bump: u8,
+    pub sas_program: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub rank_hooks: Option<AccountInfo<'info>>,
+    pub hook_authority: Option<(AccountInfo<'info>, u8)>,
+    pub hook_programs: &'a [AccountInfo<'info>],
+}
+
+impl NewIdentity<'_, '_> {
+    /// Checks the owner has no other identity, burns `burn_amount`, fills
+    /// in the AtomID and issues its attestation, then notifies rank hooks.
+    pub fn create(
+        self,
+        kind: IdentityKind,
+        burn_amount: u64,
+        metadata: Option<String>,
+        referrer: Option<Pubkey>,
+    ) -> Result<()> {
+        let config = self.config;
+
+        config.require_not_paused()?;
+
+        // A wallet linked to another identity resolves to that identity
+        require!(
+            self.wallet_link.data_is_empty(),
+            ErrorCode::WalletAlreadyLinked
+        );
+
+        // A compressed AtomID must be promoted rather than created again
+        require!(
+            self.compressed_atomid.data_is_empty(),
+            ErrorCode::WalletHasCompressedAtomId
+        );
+
+        // A linked attestation already sits at the attestation address
+        require!(
+            self.linked_attestation.data_is_empty(),
+            ErrorCode::WalletHasLinkedAttestation
+        );
+
+        require!(
+            self.atom_mint.key() == config.burn_mint,
+            ErrorCode::InvalidBurnMint
+        );
+
+        require!(
+            self.token_program.key() == anchor_spl::token::ID,
+            ErrorCode::InvalidTokenProgram
+        );
+
+        require!(
+            burn_amount >= config.min_create_burn,
+            ErrorCode::InsufficientBurnAmount
+        );
+
+        if let Some(ref meta) = metadata {
+            require!(meta.len() <= 200, ErrorCode::MetadataTooLong);
+        }
+
+        require!(
+            self.sas_credential.key() == config.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
+
+        require!(
+            self.sas_schema.key() == config.sas_schema,
+            ErrorCode::InvalidSasSchema
+        );
+
+        require!(
+            self.sas_authority.key() == config.sas_authority,
+            ErrorCode::InvalidSasAuthority
+        );
+
+        token::burn(
+            CpiContext::new(
+                self.token_program.clone(),
+                Burn {
+                    mint: self.atom_mint.clone(),
+                    from: self.owner_token_account.clone(),
+                    authority: self.owner.clone(),
+                },
+            ),
+            burn_amount,
+        )?;
+
+        let current_slot = Clock::get()?.slot;
+        let atom_id = &mut *self.atom_id;
+
+        atom_id.owner = self.owner.key();
+        atom_id.kind = kind;
+        atom_id.total_burned = burn_amount;
+        atom_id.rank = config.rank_for_kind(kind, burn_amount);
+        atom_id.metadata = metadata.unwrap_or_default();
+        atom_id.created_at_slot = current_slot;
+        atom_id.updated_at_slot = current_slot;
+        atom_id.burn_count = 1;
+        atom_id.referrer = referrer;
+        atom_id.bump = self.atom_id_bump;
+        atom_id.layout_version = ATOM_ID_LAYOUT_VERSION;
+
+        let event = ReputationEvent::Created {
+            total_burned: atom_id.total_burned,
+            rank: atom_id.rank,
+            metadata_hash: metadata_hash(&atom_id.metadata),
+            slot: current_slot,
+        };
+        atom_id.record_reputation(event)?;
+
+        let attestation_data = serialize_atomid_attestation_data(config.sas_schema_version, atom_id)?;
+
+        let expiry_timestamp = Clock::get()?.unix_timestamp + ATTESTATION_VALIDITY_SECONDS;
+
+        let attestation_ix = create_attestation_instruction(
+            self.payer.key(),
+            self.sas_authority.key(),
+            config.sas_credential,
+            config.sas_schema,
+            self.sas_attestation.key(),
+            self.system_program.key(),
+            self.owner.key(),
+            attestation_data,
+            expiry_timestamp,
+        )?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"sas_authority",
+            &[self.sas_authority_bump],
+        ]];
+
+        let account_infos = [
+            self.payer.clone(),
+            self.sas_authority.clone(),
+            self.sas_credential.clone(),
+            self.sas_schema.clone(),
+            self.sas_attestation.clone(),
+            self.system_program.clone(),
+            self.sas_program.clone(),
+        ];
+
+        anchor_lang::solana_program::program::invoke_signed(
+            &attestation_ix,
+            &account_infos,
+            signer_seeds,
+        )?;
+
+        emit!(AtomIdCreated {
+            owner: atom_id.owner,
+            total_burned: atom_id.total_burned,
+            rank: atom_id.rank,
+        });
+
+        let (owner, rank) = (atom_id.owner, atom_id.rank);
+
+        // Hooks may read the AtomID, so write it out first. A new identity
+        // counts as a change from rank 0
+        self.atom_id.exit(&crate::ID)?;
+        notify_rank_changed(
+            config,
+            self.rank_hooks.as_ref(),
+            self.hook_authority
+                .as_ref()
+                .map(|(authority, bump)| (authority, *bump)),
+            self.hook_programs,
+            &self.atom_id.to_account_info(),
+            owner,
+            0,
+            rank,
+        )
+    }
+}
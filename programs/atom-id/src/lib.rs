@@ -1,11 +1,46 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Burn, Token};
+use anchor_spl::associated_token;
+use anchor_spl::token::{self, Burn, Mint, Token};
+use anchor_spl::token_2022::{self as token_2022, Token2022};
 
+mod badge;
+mod compression;
+mod governance;
+mod guild;
+mod handle;
+mod hooks;
+mod identity;
+mod migration;
+mod moderation;
+mod profile;
+mod rank;
+mod referral;
+mod reputation;
+mod sas_schema;
 mod sas_integration;
+use badge::*;
+use compression::*;
+use governance::*;
+use guild::*;
+use handle::*;
+use hooks::*;
+use identity::*;
+use migration::*;
+use moderation::*;
+use profile::*;
+use rank::*;
+use referral::*;
+use reputation::*;
 use sas_integration::*;
 
 declare_id!("rnc2fycemiEgj4YbMSuwKFpdV6nkJonojCXib3j2by6");
 
+/// Delay applied to queued config changes on a fresh deployment.
+pub const DEFAULT_CONFIG_TIMELOCK: i64 = 2 * 24 * 60 * 60;
+pub const MAX_CONFIG_TIMELOCK: i64 = 30 * 24 * 60 * 60;
+
+pub const MAX_LINKED_WALLETS: usize = 5;
+
 #[program]
 pub mod atom_id {
     use super::*;
@@ -19,27 +54,27 @@ pub mod atom_id {
         sas_schema: Pubkey,
         sas_authority: Pubkey,
     ) -> Result<()> {
-        require!(
-            rank_thresholds.len() <= 10,
-            ErrorCode::TooManyRankThresholds
-        );
-
-        let mut sorted_thresholds = rank_thresholds.clone();
-        sorted_thresholds.sort();
-        require!(
-            sorted_thresholds == rank_thresholds,
-            ErrorCode::RankThresholdsNotSorted
-        );
+        validate_rank_thresholds(&rank_thresholds)?;
 
         let config = &mut ctx.accounts.atom_config;
         config.admin = ctx.accounts.admin.key();
+        config.config_manager = config.admin;
+        config.pauser = config.admin;
+        config.sas_manager = config.admin;
+        config.reward_funder = config.admin;
+        config.moderator = config.admin;
+        config.paused = false;
         config.min_create_burn = min_create_burn;
         config.rank_thresholds = rank_thresholds;
+        config.rank_curve = RankCurve::default();
         config.burn_mint = burn_mint;
         config.sas_credential = sas_credential;
         config.sas_schema = sas_schema;
         config.sas_authority = sas_authority;
+        config.sas_schema_version = ATTESTATION_SCHEMA_VERSION;
+        config.config_timelock = DEFAULT_CONFIG_TIMELOCK;
         config.bump = ctx.bumps.atom_config;
+        config.layout_version = CONFIG_LAYOUT_VERSION;
 
         emit!(ConfigInitialized {
             admin: config.admin,
@@ -77,6 +112,7 @@ pub mod atom_id {
                 ctx.accounts.sas_authority.to_account_info(),
                 ctx.accounts.sas_credential.to_account_info(),
                 ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
             ],
             signer_seeds,
         )?;
@@ -88,9 +124,9 @@ pub mod atom_id {
         ctx: Context<InitializeSasSchema>,
         name: String,
         description: String,
-        layout: Vec<u8>,
-        field_names: Vec<String>,
     ) -> Result<()> {
+        let (layout, field_names) = atomid_schema_layout(ATTESTATION_SCHEMA_VERSION)?;
+
         let schema_ix = create_schema_instruction(
             ctx.accounts.payer.key(),
             ctx.accounts.sas_authority.key(),
@@ -116,20 +152,194 @@ pub mod atom_id {
                 ctx.accounts.sas_credential.to_account_info(),
                 ctx.accounts.sas_schema.to_account_info(),
                 ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        Ok(())
+    }
+
+    /// Creates the schema guild attestations are issued under and records
+    /// it in the config. SAS manager only, once.
+    pub fn initialize_guild_sas_schema(
+        ctx: Context<InitializeGuildSasSchema>,
+        name: String,
+        description: String,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.atom_config.guild_sas_schema == Pubkey::default(),
+            ErrorCode::InvalidSasSchema
+        );
+
+        let (layout, field_names) = guild_schema_layout();
+
+        let schema_ix = create_schema_instruction(
+            ctx.accounts.sas_manager.key(),
+            ctx.accounts.sas_authority.key(),
+            ctx.accounts.sas_credential.key(),
+            ctx.accounts.sas_schema.key(),
+            ctx.accounts.system_program.key(),
+            name,
+            description,
+            layout,
+            field_names,
+        )?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"sas_authority",
+            &[ctx.bumps.sas_authority],
+        ]];
+
+        anchor_lang::solana_program::program::invoke_signed(
+            &schema_ix,
+            &[
+                ctx.accounts.sas_manager.to_account_info(),
+                ctx.accounts.sas_authority.to_account_info(),
+                ctx.accounts.sas_credential.to_account_info(),
+                ctx.accounts.sas_schema.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
             ],
             signer_seeds,
         )?;
 
+        ctx.accounts.atom_config.guild_sas_schema = ctx.accounts.sas_schema.key();
+
+        Ok(())
+    }
+
+    pub fn create_atomid<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateAtomId<'info>>,
+        burn_amount: u64,
+        metadata: Option<String>,
+        referrer: Option<Pubkey>,
+    ) -> Result<()> {
+        let user = ctx.accounts.user.key();
+
+        NewIdentity {
+            config: &ctx.accounts.atom_config,
+            atom_id: &mut ctx.accounts.atom_id,
+            atom_id_bump: ctx.bumps.atom_id,
+            owner: ctx.accounts.user.to_account_info(),
+            payer: ctx.accounts.user.to_account_info(),
+            wallet_link: ctx.accounts.wallet_link.to_account_info(),
+            compressed_atomid: ctx.accounts.compressed_atomid.to_account_info(),
+            linked_attestation: ctx.accounts.linked_attestation.to_account_info(),
+            owner_token_account: ctx.accounts.user_token_account.to_account_info(),
+            atom_mint: ctx.accounts.atom_mint.to_account_info(),
+            sas_attestation: ctx.accounts.sas_attestation.to_account_info(),
+            sas_credential: ctx.accounts.sas_credential.to_account_info(),
+            sas_schema: ctx.accounts.sas_schema.to_account_info(),
+            sas_authority: ctx.accounts.sas_authority.to_account_info(),
+            sas_authority_bump: ctx.bumps.sas_authority,
+            sas_program: ctx.accounts.sas_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            rank_hooks: ctx.accounts.rank_hooks.as_ref().map(|hooks| hooks.to_account_info()),
+            hook_authority: ctx
+                .accounts
+                .hook_authority
+                .as_ref()
+                .map(|authority| authority.to_account_info())
+                .zip(ctx.bumps.hook_authority),
+            hook_programs: ctx.remaining_accounts,
+        }
+        .create(IdentityKind::Individual, burn_amount, metadata, referrer)?;
+
+        // Credited once the new identity exists
+        match (referrer, ctx.accounts.referrer_atom_id.as_ref()) {
+            (Some(referrer), Some(referrer_info)) => {
+                let referrer_info = referrer_info.to_account_info();
+                let mut referrer_atom_id = load_atom_id(
+                    &referrer_info,
+                    &ctx.accounts.user.to_account_info(),
+                    &ctx.accounts.system_program.to_account_info(),
+                )?;
+                require_keys_eq!(referrer_atom_id.owner, referrer, ErrorCode::InvalidReferrer);
+                require_keys_neq!(referrer, user, ErrorCode::InvalidReferrer);
+                referrer_atom_id.require_active()?;
+
+                referrer_atom_id.referral_count = referrer_atom_id
+                    .referral_count
+                    .checked_add(1)
+                    .ok_or(ErrorCode::Overflow)?;
+                referrer_atom_id.credit_referral(user, burn_amount)?;
+                store_atom_id(&referrer_info, &referrer_atom_id)?;
+            }
+            (None, None) => {}
+            _ => return err!(ErrorCode::InvalidReferrer),
+        }
+
         Ok(())
     }
 
-    pub fn create_atomid(
-        ctx: Context<CreateAtomId>,
+    /// Creates an organization identity owned by `owner`, a PDA of another
+    /// program that signs through CPI. `payer` funds the accounts so the PDA
+    /// needn't hold lamports.
+    pub fn create_organization_atomid<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateOrganizationAtomId<'info>>,
+        burn_amount: u64,
+        metadata: Option<String>,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.owner.key().is_on_curve(),
+            ErrorCode::OwnerNotProgramAddress
+        );
+
+        NewIdentity {
+            config: &ctx.accounts.atom_config,
+            atom_id: &mut ctx.accounts.atom_id,
+            atom_id_bump: ctx.bumps.atom_id,
+            owner: ctx.accounts.owner.to_account_info(),
+            payer: ctx.accounts.payer.to_account_info(),
+            wallet_link: ctx.accounts.wallet_link.to_account_info(),
+            compressed_atomid: ctx.accounts.compressed_atomid.to_account_info(),
+            linked_attestation: ctx.accounts.linked_attestation.to_account_info(),
+            owner_token_account: ctx.accounts.owner_token_account.to_account_info(),
+            atom_mint: ctx.accounts.atom_mint.to_account_info(),
+            sas_attestation: ctx.accounts.sas_attestation.to_account_info(),
+            sas_credential: ctx.accounts.sas_credential.to_account_info(),
+            sas_schema: ctx.accounts.sas_schema.to_account_info(),
+            sas_authority: ctx.accounts.sas_authority.to_account_info(),
+            sas_authority_bump: ctx.bumps.sas_authority,
+            sas_program: ctx.accounts.sas_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            rank_hooks: ctx.accounts.rank_hooks.as_ref().map(|hooks| hooks.to_account_info()),
+            hook_authority: ctx
+                .accounts
+                .hook_authority
+                .as_ref()
+                .map(|authority| authority.to_account_info())
+                .zip(ctx.bumps.hook_authority),
+            hook_programs: ctx.remaining_accounts,
+        }
+        .create(IdentityKind::Organization, burn_amount, metadata, None)
+    }
+
+    /// Burns `burn_amount` more and recomputes the rank. A zero burn is a
+    /// rank refresh, e.g. after the thresholds or curve change, which config
+    /// changes can't apply to every identity themselves.
+    pub fn upgrade_atomid<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpgradeAtomId<'info>>,
         burn_amount: u64,
         metadata: Option<String>,
     ) -> Result<()> {
         let config = &ctx.accounts.atom_config;
 
+        config.require_not_paused()?;
+
+        // Identities created before the current layout are migrated here,
+        // so old clients keep working without calling `migrate_atomid`
+        let mut atom_id = load_atom_id(
+            &ctx.accounts.atom_id.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+        require_keys_eq!(atom_id.owner, ctx.accounts.user.key(), ErrorCode::Unauthorized);
+        atom_id.require_active()?;
+
         require!(
             ctx.accounts.atom_mint.key() == config.burn_mint,
             ErrorCode::InvalidBurnMint
@@ -140,11 +350,6 @@ pub mod atom_id {
             ErrorCode::InvalidTokenProgram
         );
 
-        require!(
-            burn_amount >= config.min_create_burn,
-            ErrorCode::InsufficientBurnAmount
-        );
-
         if let Some(ref meta) = metadata {
             require!(meta.len() <= 200, ErrorCode::MetadataTooLong);
         }
@@ -164,39 +369,197 @@ pub mod atom_id {
             ErrorCode::InvalidSasAuthority
         );
 
-        token::burn(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Burn {
-                    mint: ctx.accounts.atom_mint.to_account_info(),
-                    from: ctx.accounts.user_token_account.to_account_info(),
-                    authority: ctx.accounts.user.to_account_info(),
-                },
-            ),
+        // Attestations are keyed by the user, so the one being replaced and
+        // its replacement live at the same address
+        let (attestation, _) = derive_attestation_pda(
+            &config.sas_credential,
+            &config.sas_schema,
+            &ctx.accounts.user.key(),
+        );
+        require_keys_eq!(
+            ctx.accounts.sas_attestation.key(),
+            attestation,
+            ErrorCode::InvalidAttestationAddress
+        );
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"sas_authority",
+            &[ctx.bumps.sas_authority],
+        ]];
+
+        // An attestation that was already closed (e.g. after expiring) is
+        // simply reissued below
+        if attestation_exists(&ctx.accounts.sas_attestation) {
+            let close_ix = close_attestation_instruction(
+                ctx.accounts.user.key(),
+                ctx.accounts.sas_authority.key(),
+                config.sas_credential,
+                ctx.accounts.sas_attestation.key(),
+                ctx.accounts.sas_event_authority.key(),
+                ctx.accounts.system_program.key(),
+            )?;
+
+            anchor_lang::solana_program::program::invoke_signed(
+                &close_ix,
+                &[
+                    ctx.accounts.user.to_account_info(),
+                    ctx.accounts.sas_authority.to_account_info(),
+                    ctx.accounts.sas_credential.to_account_info(),
+                    ctx.accounts.sas_attestation.to_account_info(),
+                    ctx.accounts.sas_event_authority.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                    ctx.accounts.sas_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        } else {
+            msg!("No existing attestation to close, reissuing");
+        }
+
+        // After a schema change the attestation under the previous schema
+        // would otherwise keep advertising the old rank until migrated
+        if config.previous_sas_schema != Pubkey::default() {
+            let (Some(previous_credential), Some(previous_attestation)) = (
+                ctx.accounts.previous_sas_credential.as_ref(),
+                ctx.accounts.previous_sas_attestation.as_ref(),
+            ) else {
+                return err!(ErrorCode::PreviousAttestationRequired);
+            };
+
+            require!(
+                previous_credential.key() == config.previous_sas_credential,
+                ErrorCode::InvalidSasCredential
+            );
+
+            let (previous, _) = derive_attestation_pda(
+                &config.previous_sas_credential,
+                &config.previous_sas_schema,
+                &ctx.accounts.user.key(),
+            );
+            require_keys_eq!(
+                previous_attestation.key(),
+                previous,
+                ErrorCode::InvalidAttestationAddress
+            );
+
+            if attestation_exists(previous_attestation) {
+                let close_ix = close_attestation_instruction(
+                    ctx.accounts.user.key(),
+                    ctx.accounts.sas_authority.key(),
+                    config.previous_sas_credential,
+                    previous_attestation.key(),
+                    ctx.accounts.sas_event_authority.key(),
+                    ctx.accounts.system_program.key(),
+                )?;
+
+                anchor_lang::solana_program::program::invoke_signed(
+                    &close_ix,
+                    &[
+                        ctx.accounts.user.to_account_info(),
+                        ctx.accounts.sas_authority.to_account_info(),
+                        previous_credential.to_account_info(),
+                        previous_attestation.to_account_info(),
+                        ctx.accounts.sas_event_authority.to_account_info(),
+                        ctx.accounts.system_program.to_account_info(),
+                        ctx.accounts.sas_program.to_account_info(),
+                    ],
+                    signer_seeds,
+                )?;
+            }
+        }
+
+        // A zero burn only refreshes the rank under the current thresholds
+        // and curve, reissuing the attestation and notifying hooks
+        let refresh_only = burn_amount == 0;
+
+        if !refresh_only {
+            token::burn(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    Burn {
+                        mint: ctx.accounts.atom_mint.to_account_info(),
+                        from: ctx.accounts.user_token_account.to_account_info(),
+                        authority: ctx.accounts.user.to_account_info(),
+                    },
+                ),
+                burn_amount,
+            )?;
+        }
+
+        let old_rank = atom_id.rank;
+
+        if !refresh_only {
+            atom_id.total_burned = atom_id
+                .total_burned
+                .checked_add(burn_amount)
+                .ok_or(ErrorCode::Overflow)?;
+            atom_id.burn_count = atom_id
+                .burn_count
+                .checked_add(1)
+                .ok_or(ErrorCode::Overflow)?;
+        }
+
+        atom_id.rank = config.rank_for_kind(atom_id.kind, atom_id.total_burned);
+        atom_id.updated_at_slot = Clock::get()?.slot;
+
+        if let Some(meta) = metadata {
+            atom_id.metadata = meta;
+        }
+
+        let event = ReputationEvent::Upgraded {
             burn_amount,
-        )?;
+            total_burned: atom_id.total_burned,
+            rank: atom_id.rank,
+            metadata_hash: metadata_hash(&atom_id.metadata),
+            slot: atom_id.updated_at_slot,
+        };
+        atom_id.record_reputation(event)?;
 
-        let current_slot = Clock::get()?.slot;
-        let atom_id = &mut ctx.accounts.atom_id;
+        if let Some(referrer) = atom_id.referrer.filter(|_| !refresh_only) {
+            let referrer_info = ctx
+                .accounts
+                .referrer_atom_id
+                .as_ref()
+                .ok_or(ErrorCode::InvalidReferrer)?
+                .to_account_info();
+            let mut referrer_atom_id = load_atom_id(
+                &referrer_info,
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
+            require_keys_eq!(referrer_atom_id.owner, referrer, ErrorCode::InvalidReferrer);
+            referrer_atom_id.credit_referral(atom_id.owner, burn_amount)?;
+            store_atom_id(&referrer_info, &referrer_atom_id)?;
+        }
 
-        atom_id.owner = ctx.accounts.user.key();
-        atom_id.total_burned = burn_amount;
-        atom_id.rank = calculate_rank(burn_amount, &config.rank_thresholds);
-        atom_id.metadata = metadata.unwrap_or_default();
-        atom_id.created_at_slot = current_slot;
-        atom_id.updated_at_slot = current_slot;
-        atom_id.bump = ctx.bumps.atom_id;
+        match (ctx.accounts.guild.as_mut(), ctx.accounts.guild_member.as_mut()) {
+            (Some(_), Some(_)) if refresh_only => {}
+            (Some(guild), Some(member)) => {
+                require_keys_eq!(member.guild, guild.key(), ErrorCode::NotGuildMember);
+                member.contributed = member
+                    .contributed
+                    .checked_add(burn_amount)
+                    .ok_or(ErrorCode::Overflow)?;
 
-        let attestation_data = serialize_atomid_attestation_data(
-            atom_id.rank,
-            atom_id.total_burned,
-            atom_id.created_at_slot,
-        );
+                let old_guild_rank = guild.rank;
+                guild.record_contribution(config, burn_amount, atom_id.updated_at_slot)?;
+
+                emit!(GuildContribution {
+                    guild: guild.key(),
+                    member: member.member,
+                    burn_amount,
+                    total_burned: guild.total_burned,
+                    old_rank: old_guild_rank,
+                    new_rank: guild.rank,
+                });
+            }
+            (None, None) => {}
+            _ => return err!(ErrorCode::NotGuildMember),
+        }
 
-        msg!("Attestation data length: {}", attestation_data.len());
-        msg!("Attestation data: {:?}", attestation_data);
+        let attestation_data = serialize_atomid_attestation_data(config.sas_schema_version, &atom_id)?;
 
-        let expiry_timestamp = Clock::get()?.unix_timestamp + (365 * 24 * 60 * 60);
+        let expiry_timestamp = Clock::get()?.unix_timestamp + ATTESTATION_VALIDITY_SECONDS;
 
         let attestation_ix = create_attestation_instruction(
             ctx.accounts.user.key(),
@@ -215,53 +578,115 @@ pub mod atom_id {
             &[ctx.bumps.sas_authority],
         ]];
 
-        let account_infos = [
-            ctx.accounts.user.to_account_info(),
-            ctx.accounts.sas_authority.to_account_info(),
-            ctx.accounts.sas_credential.to_account_info(),
-            ctx.accounts.sas_schema.to_account_info(),
-            ctx.accounts.sas_attestation.to_account_info(),
-            ctx.accounts.system_program.to_account_info(),
-        ];
-
         anchor_lang::solana_program::program::invoke_signed(
             &attestation_ix,
-            &account_infos,
+            &[
+                ctx.accounts.user.to_account_info(),
+                ctx.accounts.sas_authority.to_account_info(),
+                ctx.accounts.sas_credential.to_account_info(),
+                ctx.accounts.sas_schema.to_account_info(),
+                ctx.accounts.sas_attestation.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
+            ],
             signer_seeds,
         )?;
 
-        emit!(AtomIdCreated {
+        if atom_id.has_badge {
+            let (
+                Some(badge_mint),
+                Some(badge_authority),
+                Some(token_2022_program),
+                Some(authority_bump),
+            ) = (
+                ctx.accounts.badge_mint.as_ref(),
+                ctx.accounts.badge_authority.as_ref(),
+                ctx.accounts.token_2022_program.as_ref(),
+                ctx.bumps.badge_authority,
+            )
+            else {
+                return err!(ErrorCode::MissingBadgeAccounts);
+            };
+
+            // The badge shows the artwork of the tier it's at, so a rank
+            // change needs the new tier whenever one exists
+            let uri = match ctx.accounts.rank_tier.as_ref() {
+                Some(tier) => {
+                    require!(tier.rank == atom_id.rank, ErrorCode::RankOutOfRange);
+                    Some(tier.badge_uri.clone())
+                }
+                None => {
+                    require!(
+                        atom_id.rank == old_rank
+                            || config.rank_tier_mask & rank_tier_bit(atom_id.rank) == 0,
+                        ErrorCode::MissingBadgeAccounts
+                    );
+                    None
+                }
+            };
+
+            update_badge_fields(
+                &ctx.accounts.user.to_account_info(),
+                &badge_mint.to_account_info(),
+                &badge_authority.to_account_info(),
+                &token_2022_program.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                &[b"badge_authority", &[authority_bump]],
+                uri,
+                atom_id.rank,
+                atom_id.total_burned,
+            )?;
+        }
+
+        // Hooks may read the AtomID, so write the new state out first
+        store_atom_id(&ctx.accounts.atom_id.to_account_info(), &atom_id)?;
+
+        notify_rank_changed(
+            config,
+            ctx.accounts.rank_hooks.as_ref().map(|hooks| hooks.as_ref()),
+            ctx.accounts
+                .hook_authority
+                .as_ref()
+                .map(|authority| authority.as_ref())
+                .zip(ctx.bumps.hook_authority),
+            ctx.remaining_accounts,
+            &ctx.accounts.atom_id.to_account_info(),
+            atom_id.owner,
+            old_rank,
+            atom_id.rank,
+        )?;
+
+        emit!(AtomIdUpgraded {
             owner: atom_id.owner,
             total_burned: atom_id.total_burned,
-            rank: atom_id.rank,
+            old_rank,
+            new_rank: atom_id.rank,
         });
 
         Ok(())
     }
 
-    pub fn upgrade_atomid(
-        ctx: Context<UpgradeAtomId>,
-        burn_amount: u64,
-        metadata: Option<String>,
-    ) -> Result<()> {
+    /// Permissionless: moves an attestation issued under the previous schema
+    /// onto the current one, re-encoding the payload for the current version.
+    /// Safe to repeat; an identity that already has a current-schema
+    /// attestation (e.g. from `upgrade_atomid`) only has the old one closed.
+    pub fn migrate_attestation(ctx: Context<MigrateAttestation>) -> Result<()> {
         let config = &ctx.accounts.atom_config;
+        config.require_not_paused()?;
+
+        let atom_id = &ctx.accounts.atom_id;
+        atom_id.require_active()?;
 
         require!(
-            ctx.accounts.atom_mint.key() == config.burn_mint,
-            ErrorCode::InvalidBurnMint
+            config.previous_sas_schema != Pubkey::default(),
+            ErrorCode::NoPreviousSchema
         );
 
         require!(
-            ctx.accounts.token_program.key() == anchor_spl::token::ID,
-            ErrorCode::InvalidTokenProgram
+            ctx.accounts.previous_sas_credential.key() == config.previous_sas_credential,
+            ErrorCode::InvalidSasCredential
         );
 
-        require!(burn_amount > 0, ErrorCode::InsufficientBurnAmount);
-
-        if let Some(ref meta) = metadata {
-            require!(meta.len() <= 200, ErrorCode::MetadataTooLong);
-        }
-
         require!(
             ctx.accounts.sas_credential.key() == config.sas_credential,
             ErrorCode::InvalidSasCredential
@@ -277,77 +702,154 @@ pub mod atom_id {
             ErrorCode::InvalidSasAuthority
         );
 
-        let close_ix = close_attestation_instruction(
-            ctx.accounts.user.key(),
-            ctx.accounts.sas_authority.key(),
-            config.sas_credential,
+        let (old_attestation, _) = derive_attestation_pda(
+            &config.previous_sas_credential,
+            &config.previous_sas_schema,
+            &atom_id.owner,
+        );
+        require_keys_eq!(
             ctx.accounts.old_sas_attestation.key(),
-            ctx.accounts.sas_event_authority.key(),
-            ctx.accounts.system_program.key(),
-        )?;
+            old_attestation,
+            ErrorCode::InvalidAttestationAddress
+        );
+
+        let (new_attestation, _) =
+            derive_attestation_pda(&config.sas_credential, &config.sas_schema, &atom_id.owner);
+        require_keys_eq!(
+            ctx.accounts.new_sas_attestation.key(),
+            new_attestation,
+            ErrorCode::InvalidAttestationAddress
+        );
 
         let signer_seeds: &[&[&[u8]]] = &[&[
             b"sas_authority",
             &[ctx.bumps.sas_authority],
         ]];
 
-        anchor_lang::solana_program::program::invoke_signed(
-            &close_ix,
-            &[
-                ctx.accounts.user.to_account_info(),
-                ctx.accounts.sas_authority.to_account_info(),
-                ctx.accounts.sas_credential.to_account_info(),
-                ctx.accounts.old_sas_attestation.to_account_info(),
-                ctx.accounts.sas_event_authority.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-                ctx.accounts.sas_program.to_account_info(),
-            ],
-            signer_seeds,
-        )?;
+        if attestation_exists(&ctx.accounts.old_sas_attestation) {
+            let close_ix = close_attestation_instruction(
+                ctx.accounts.payer.key(),
+                ctx.accounts.sas_authority.key(),
+                config.previous_sas_credential,
+                ctx.accounts.old_sas_attestation.key(),
+                ctx.accounts.sas_event_authority.key(),
+                ctx.accounts.system_program.key(),
+            )?;
 
-        token::burn(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Burn {
-                    mint: ctx.accounts.atom_mint.to_account_info(),
-                    from: ctx.accounts.user_token_account.to_account_info(),
-                    authority: ctx.accounts.user.to_account_info(),
-                },
-            ),
-            burn_amount,
-        )?;
+            anchor_lang::solana_program::program::invoke_signed(
+                &close_ix,
+                &[
+                    ctx.accounts.payer.to_account_info(),
+                    ctx.accounts.sas_authority.to_account_info(),
+                    ctx.accounts.previous_sas_credential.to_account_info(),
+                    ctx.accounts.old_sas_attestation.to_account_info(),
+                    ctx.accounts.sas_event_authority.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                    ctx.accounts.sas_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        } else {
+            msg!("No attestation under the previous schema, issuing fresh");
+        }
 
-        let atom_id = &mut ctx.accounts.atom_id;
-        let old_rank = atom_id.rank;
+        if attestation_exists(&ctx.accounts.new_sas_attestation) {
+            msg!("Attestation already on the current schema");
+        } else {
+            let attestation_data = serialize_atomid_attestation_data(config.sas_schema_version, atom_id)?;
 
-        atom_id.total_burned = atom_id
-            .total_burned
-            .checked_add(burn_amount)
-            .ok_or(ErrorCode::Overflow)?;
+            let expiry_timestamp = Clock::get()?.unix_timestamp + ATTESTATION_VALIDITY_SECONDS;
 
-        atom_id.rank = calculate_rank(atom_id.total_burned, &config.rank_thresholds);
-        atom_id.updated_at_slot = Clock::get()?.slot;
+            let attestation_ix = create_attestation_instruction(
+                ctx.accounts.payer.key(),
+                ctx.accounts.sas_authority.key(),
+                config.sas_credential,
+                config.sas_schema,
+                ctx.accounts.new_sas_attestation.key(),
+                ctx.accounts.system_program.key(),
+                atom_id.owner,
+                attestation_data,
+                expiry_timestamp,
+            )?;
 
-        if let Some(meta) = metadata {
-            atom_id.metadata = meta;
+            anchor_lang::solana_program::program::invoke_signed(
+                &attestation_ix,
+                &[
+                    ctx.accounts.payer.to_account_info(),
+                    ctx.accounts.sas_authority.to_account_info(),
+                    ctx.accounts.sas_credential.to_account_info(),
+                    ctx.accounts.sas_schema.to_account_info(),
+                    ctx.accounts.new_sas_attestation.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                    ctx.accounts.sas_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
         }
 
-        let attestation_data = serialize_atomid_attestation_data(
-            atom_id.rank,
-            atom_id.total_burned,
-            atom_id.created_at_slot,
+        emit!(AttestationMigrated {
+            owner: atom_id.owner,
+            from_schema: config.previous_sas_schema,
+            to_schema: config.sas_schema,
+            version: config.sas_schema_version,
+        });
+
+        Ok(())
+    }
+
+    /// Issues an extra attestation of the caller's identity to another wallet,
+    /// so the identity shows up there without that wallet holding the burns.
+    pub fn issue_linked_attestation(ctx: Context<IssueLinkedAttestation>) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+        config.require_not_paused()?;
+
+        let atom_id = &ctx.accounts.atom_id;
+        let recipient = ctx.accounts.recipient.key();
+        atom_id.require_active()?;
+
+        require_keys_neq!(recipient, atom_id.owner, ErrorCode::InvalidRecipient);
+
+        // A wallet with its own AtomID already holds the attestation at this nonce
+        require!(
+            ctx.accounts.recipient_atom_id.data_is_empty(),
+            ErrorCode::WalletHasAtomId
+        );
+
+        require!(
+            ctx.accounts.sas_credential.key() == config.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
+
+        require!(
+            ctx.accounts.sas_schema.key() == config.sas_schema,
+            ErrorCode::InvalidSasSchema
+        );
+
+        require!(
+            ctx.accounts.sas_authority.key() == config.sas_authority,
+            ErrorCode::InvalidSasAuthority
+        );
+
+        let (attestation, _) =
+            derive_attestation_pda(&config.sas_credential, &config.sas_schema, &recipient);
+        require_keys_eq!(
+            ctx.accounts.sas_attestation.key(),
+            attestation,
+            ErrorCode::InvalidAttestationAddress
         );
 
-        let expiry_timestamp = Clock::get()?.unix_timestamp + (365 * 24 * 60 * 60);
+        let attestation_data = serialize_atomid_attestation_data(config.sas_schema_version, atom_id)?;
+
+        let expiry_timestamp = Clock::get()?.unix_timestamp + ATTESTATION_VALIDITY_SECONDS;
 
         let attestation_ix = create_attestation_instruction(
             ctx.accounts.user.key(),
             ctx.accounts.sas_authority.key(),
             config.sas_credential,
             config.sas_schema,
-            ctx.accounts.new_sas_attestation.key(),
+            ctx.accounts.sas_attestation.key(),
             ctx.accounts.system_program.key(),
-            ctx.accounts.user.key(),
+            recipient,
             attestation_data,
             expiry_timestamp,
         )?;
@@ -364,335 +866,4118 @@ pub mod atom_id {
                 ctx.accounts.sas_authority.to_account_info(),
                 ctx.accounts.sas_credential.to_account_info(),
                 ctx.accounts.sas_schema.to_account_info(),
-                ctx.accounts.new_sas_attestation.to_account_info(),
+                ctx.accounts.sas_attestation.to_account_info(),
                 ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
             ],
             signer_seeds,
         )?;
 
-        emit!(AtomIdUpgraded {
-            owner: atom_id.owner,
-            total_burned: atom_id.total_burned,
-            old_rank,
-            new_rank: atom_id.rank,
+        let atom_id = &mut ctx.accounts.atom_id;
+        atom_id.linked_attestation_count = atom_id
+            .linked_attestation_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let linked = &mut ctx.accounts.linked_attestation;
+        linked.owner = atom_id.owner;
+        linked.recipient = recipient;
+        linked.sas_credential = config.sas_credential;
+        linked.sas_schema = config.sas_schema;
+        linked.created_at_slot = Clock::get()?.slot;
+        linked.bump = ctx.bumps.linked_attestation;
+
+        emit!(LinkedAttestationIssued {
+            owner: linked.owner,
+            recipient,
         });
 
         Ok(())
     }
 
-    pub fn update_metadata(
-        ctx: Context<UpdateMetadata>,
-        new_metadata: String,
-    ) -> Result<()> {
+    /// Permissionless: reissues a linked attestation with the identity's
+    /// current data and schema, e.g. after an upgrade.
+    pub fn refresh_linked_attestation(ctx: Context<RefreshLinkedAttestation>) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+        config.require_not_paused()?;
+
+        let atom_id = &ctx.accounts.atom_id;
+        let linked = &ctx.accounts.linked_attestation;
+        atom_id.require_active()?;
+
         require!(
-            new_metadata.len() <= 200,
-            ErrorCode::MetadataTooLong
+            ctx.accounts.linked_sas_credential.key() == linked.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
+
+        require!(
+            ctx.accounts.sas_credential.key() == config.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
+
+        require!(
+            ctx.accounts.sas_schema.key() == config.sas_schema,
+            ErrorCode::InvalidSasSchema
+        );
+
+        require!(
+            ctx.accounts.sas_authority.key() == config.sas_authority,
+            ErrorCode::InvalidSasAuthority
+        );
+
+        let (old_attestation, _) =
+            derive_attestation_pda(&linked.sas_credential, &linked.sas_schema, &linked.recipient);
+        require_keys_eq!(
+            ctx.accounts.old_sas_attestation.key(),
+            old_attestation,
+            ErrorCode::InvalidAttestationAddress
+        );
+
+        let (new_attestation, _) =
+            derive_attestation_pda(&config.sas_credential, &config.sas_schema, &linked.recipient);
+        require_keys_eq!(
+            ctx.accounts.new_sas_attestation.key(),
+            new_attestation,
+            ErrorCode::InvalidAttestationAddress
+        );
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"sas_authority",
+            &[ctx.bumps.sas_authority],
+        ]];
+
+        if attestation_exists(&ctx.accounts.old_sas_attestation) {
+            let close_ix = close_attestation_instruction(
+                ctx.accounts.payer.key(),
+                ctx.accounts.sas_authority.key(),
+                linked.sas_credential,
+                ctx.accounts.old_sas_attestation.key(),
+                ctx.accounts.sas_event_authority.key(),
+                ctx.accounts.system_program.key(),
+            )?;
+
+            anchor_lang::solana_program::program::invoke_signed(
+                &close_ix,
+                &[
+                    ctx.accounts.payer.to_account_info(),
+                    ctx.accounts.sas_authority.to_account_info(),
+                    ctx.accounts.linked_sas_credential.to_account_info(),
+                    ctx.accounts.old_sas_attestation.to_account_info(),
+                    ctx.accounts.sas_event_authority.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                    ctx.accounts.sas_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        let attestation_data = serialize_atomid_attestation_data(config.sas_schema_version, atom_id)?;
+
+        let expiry_timestamp = Clock::get()?.unix_timestamp + ATTESTATION_VALIDITY_SECONDS;
+
+        let attestation_ix = create_attestation_instruction(
+            ctx.accounts.payer.key(),
+            ctx.accounts.sas_authority.key(),
+            config.sas_credential,
+            config.sas_schema,
+            ctx.accounts.new_sas_attestation.key(),
+            ctx.accounts.system_program.key(),
+            linked.recipient,
+            attestation_data,
+            expiry_timestamp,
+        )?;
+
+        anchor_lang::solana_program::program::invoke_signed(
+            &attestation_ix,
+            &[
+                ctx.accounts.payer.to_account_info(),
+                ctx.accounts.sas_authority.to_account_info(),
+                ctx.accounts.sas_credential.to_account_info(),
+                ctx.accounts.sas_schema.to_account_info(),
+                ctx.accounts.new_sas_attestation.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let linked = &mut ctx.accounts.linked_attestation;
+        linked.sas_credential = config.sas_credential;
+        linked.sas_schema = config.sas_schema;
+
+        Ok(())
+    }
+
+    /// Closes a linked attestation. Either the identity owner or the
+    /// recipient may revoke it; rent always goes back to the owner.
+    pub fn revoke_linked_attestation(ctx: Context<RevokeLinkedAttestation>) -> Result<()> {
+        let linked = &ctx.accounts.linked_attestation;
+
+        require!(
+            ctx.accounts.sas_credential.key() == linked.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
+
+        require!(
+            ctx.accounts.sas_authority.key() == ctx.accounts.atom_config.sas_authority,
+            ErrorCode::InvalidSasAuthority
+        );
+
+        let (attestation, _) =
+            derive_attestation_pda(&linked.sas_credential, &linked.sas_schema, &linked.recipient);
+        require_keys_eq!(
+            ctx.accounts.sas_attestation.key(),
+            attestation,
+            ErrorCode::InvalidAttestationAddress
         );
 
+        if attestation_exists(&ctx.accounts.sas_attestation) {
+            let balance_before = ctx.accounts.user.lamports();
+
+            let close_ix = close_attestation_instruction(
+                ctx.accounts.user.key(),
+                ctx.accounts.sas_authority.key(),
+                linked.sas_credential,
+                ctx.accounts.sas_attestation.key(),
+                ctx.accounts.sas_event_authority.key(),
+                ctx.accounts.system_program.key(),
+            )?;
+
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                b"sas_authority",
+                &[ctx.bumps.sas_authority],
+            ]];
+
+            anchor_lang::solana_program::program::invoke_signed(
+                &close_ix,
+                &[
+                    ctx.accounts.user.to_account_info(),
+                    ctx.accounts.sas_authority.to_account_info(),
+                    ctx.accounts.sas_credential.to_account_info(),
+                    ctx.accounts.sas_attestation.to_account_info(),
+                    ctx.accounts.sas_event_authority.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                    ctx.accounts.sas_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+
+            // SAS refunds whoever closes; a recipient revoking passes the
+            // rent on to the owner who paid it
+            let refund = ctx.accounts.user.lamports().saturating_sub(balance_before);
+            if refund > 0 && ctx.accounts.user.key() != linked.owner {
+                anchor_lang::system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        anchor_lang::system_program::Transfer {
+                            from: ctx.accounts.user.to_account_info(),
+                            to: ctx.accounts.owner.to_account_info(),
+                        },
+                    ),
+                    refund,
+                )?;
+            }
+        }
+
         let atom_id = &mut ctx.accounts.atom_id;
-        atom_id.metadata = new_metadata;
-        atom_id.updated_at_slot = Clock::get()?.slot;
+        atom_id.linked_attestation_count = atom_id
+            .linked_attestation_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(LinkedAttestationRevoked {
+            owner: linked.owner,
+            recipient: linked.recipient,
+        });
 
         Ok(())
     }
 
-    pub fn admin_update_config(
-        ctx: Context<AdminUpdateConfig>,
-        min_create_burn: Option<u64>,
-        rank_thresholds: Option<Vec<u64>>,
+    /// Creates a schema with a partner's own layout under our credential and
+    /// binds it to `issuer`, the only key that can later be registered for it.
+    pub fn create_partner_schema(
+        ctx: Context<CreatePartnerSchema>,
+        name: String,
+        description: String,
+        layout: Vec<u8>,
+        field_names: Vec<String>,
     ) -> Result<()> {
-        let config = &mut ctx.accounts.atom_config;
+        validate_partner_schema_layout(&layout, &field_names)?;
 
-        if let Some(min_burn) = min_create_burn {
-            config.min_create_burn = min_burn;
-        }
+        let schema_ix = create_schema_instruction(
+            ctx.accounts.sas_manager.key(),
+            ctx.accounts.sas_authority.key(),
+            ctx.accounts.sas_credential.key(),
+            ctx.accounts.sas_schema.key(),
+            ctx.accounts.system_program.key(),
+            name,
+            description,
+            layout,
+            field_names,
+        )?;
 
-        if let Some(thresholds) = rank_thresholds {
-            require!(
-                thresholds.len() <= 10,
-                ErrorCode::TooManyRankThresholds
-            );
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"sas_authority",
+            &[ctx.bumps.sas_authority],
+        ]];
 
-            let mut sorted = thresholds.clone();
-            sorted.sort();
-            require!(
-                sorted == thresholds,
-                ErrorCode::RankThresholdsNotSorted
-            );
+        anchor_lang::solana_program::program::invoke_signed(
+            &schema_ix,
+            &[
+                ctx.accounts.sas_manager.to_account_info(),
+                ctx.accounts.sas_authority.to_account_info(),
+                ctx.accounts.sas_credential.to_account_info(),
+                ctx.accounts.sas_schema.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
 
-            config.rank_thresholds = thresholds;
-        }
+        let partner_schema = &mut ctx.accounts.partner_schema;
+        partner_schema.schema = ctx.accounts.sas_schema.key();
+        partner_schema.issuer = ctx.accounts.issuer.key();
+        partner_schema.bump = ctx.bumps.partner_schema;
+
+        emit!(PartnerSchemaCreated {
+            issuer: partner_schema.issuer,
+            sas_schema: partner_schema.schema,
+        });
 
         Ok(())
     }
 
-    /// Print security.txt to program logs for verification
-    pub fn print_security_txt(_ctx: Context<PrintSecurityTxt>) -> Result<()> {
-        let security_txt = include_str!("security.txt");
-        msg!("security_txt: {}", security_txt);
+    /// Approves `issuer` to issue attestations under our credential with the
+    /// schema created for it by `create_partner_schema`.
+    pub fn register_partner_issuer(
+        ctx: Context<RegisterPartnerIssuer>,
+        name: String,
+    ) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+        let schema = &ctx.accounts.sas_schema;
+
+        require!(
+            !name.is_empty() && name.len() <= 32,
+            ErrorCode::InvalidIssuerName
+        );
+
+        require!(
+            sas_schema_credential(schema)? == config.sas_credential,
+            ErrorCode::InvalidSasSchema
+        );
+
+        // Each schema belongs to one issuer, so no other partner can write or
+        // close attestations under it
+        let partner_schema = &ctx.accounts.partner_schema;
+        require!(
+            partner_schema.schema == schema.key()
+                && partner_schema.issuer == ctx.accounts.issuer.key(),
+            ErrorCode::InvalidSasSchema
+        );
+
+        // Partners must never be able to write AtomID attestations
+        require!(
+            schema.key() != config.sas_schema && schema.key() != config.previous_sas_schema,
+            ErrorCode::InvalidSasSchema
+        );
+
+        let issuer = &mut ctx.accounts.partner_issuer;
+        issuer.authority = ctx.accounts.issuer.key();
+        issuer.sas_schema = schema.key();
+        issuer.name = name;
+        issuer.issued_count = 0;
+        issuer.bump = ctx.bumps.partner_issuer;
+
+        emit!(PartnerIssuerRegistered {
+            authority: issuer.authority,
+            sas_schema: issuer.sas_schema,
+        });
+
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct PrintSecurityTxt {}
+    pub fn remove_partner_issuer(ctx: Context<RemovePartnerIssuer>) -> Result<()> {
+        emit!(PartnerIssuerRemoved {
+            authority: ctx.accounts.partner_issuer.authority,
+        });
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = admin,
-        space = 8 + AtomConfig::INIT_SPACE,
-        seeds = [b"atomid_config"],
-        bump
-    )]
-    pub atom_config: Account<'info, AtomConfig>,
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub admin: Signer<'info>,
+    /// Issues a partner claim about an AtomID holder. `data` must match the
+    /// partner's schema layout; SAS validates it.
+    pub fn issue_partner_attestation(
+        ctx: Context<IssuePartnerAttestation>,
+        data: Vec<u8>,
+        expiry: i64,
+    ) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+        let issuer = &ctx.accounts.partner_issuer;
+        let subject = ctx.accounts.atom_id.owner;
 
-    pub system_program: Program<'info, System>,
-}
+        config.require_not_paused()?;
+        ctx.accounts.atom_id.require_active()?;
 
-#[derive(Accounts)]
-pub struct InitializeSasCredential<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
+        require!(
+            ctx.accounts.sas_credential.key() == config.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
 
-    #[account(
-        seeds = [b"sas_authority"],
-        bump
-    )]
-    /// CHECK: PDA used to sign SAS operations
-    pub sas_authority: UncheckedAccount<'info>,
+        require!(
+            ctx.accounts.sas_schema.key() == issuer.sas_schema,
+            ErrorCode::InvalidSasSchema
+        );
+
+        require!(
+            ctx.accounts.sas_authority.key() == config.sas_authority,
+            ErrorCode::InvalidSasAuthority
+        );
+
+        require!(
+            expiry > Clock::get()?.unix_timestamp,
+            ErrorCode::InvalidExpiry
+        );
+
+        let (attestation, _) =
+            derive_attestation_pda(&config.sas_credential, &issuer.sas_schema, &subject);
+        require_keys_eq!(
+            ctx.accounts.sas_attestation.key(),
+            attestation,
+            ErrorCode::InvalidAttestationAddress
+        );
+
+        let attestation_ix = create_attestation_instruction(
+            ctx.accounts.authority.key(),
+            ctx.accounts.sas_authority.key(),
+            config.sas_credential,
+            issuer.sas_schema,
+            attestation,
+            ctx.accounts.system_program.key(),
+            subject,
+            data,
+            expiry,
+        )?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"sas_authority",
+            &[ctx.bumps.sas_authority],
+        ]];
+
+        anchor_lang::solana_program::program::invoke_signed(
+            &attestation_ix,
+            &[
+                ctx.accounts.authority.to_account_info(),
+                ctx.accounts.sas_authority.to_account_info(),
+                ctx.accounts.sas_credential.to_account_info(),
+                ctx.accounts.sas_schema.to_account_info(),
+                ctx.accounts.sas_attestation.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        let issuer = &mut ctx.accounts.partner_issuer;
+        issuer.issued_count = issuer
+            .issued_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(PartnerAttestationIssued {
+            issuer: issuer.authority,
+            subject,
+            sas_schema: issuer.sas_schema,
+            expiry,
+        });
+
+        Ok(())
+    }
+
+    /// Closes an attestation the issuer made earlier, e.g. when a claim no
+    /// longer holds.
+    pub fn revoke_partner_attestation(
+        ctx: Context<RevokePartnerAttestation>,
+        subject: Pubkey,
+    ) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+        let issuer = &ctx.accounts.partner_issuer;
+
+        require!(
+            ctx.accounts.sas_credential.key() == config.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
+
+        require!(
+            ctx.accounts.sas_authority.key() == config.sas_authority,
+            ErrorCode::InvalidSasAuthority
+        );
+
+        let (attestation, _) =
+            derive_attestation_pda(&config.sas_credential, &issuer.sas_schema, &subject);
+        require_keys_eq!(
+            ctx.accounts.sas_attestation.key(),
+            attestation,
+            ErrorCode::InvalidAttestationAddress
+        );
+
+        let close_ix = close_attestation_instruction(
+            ctx.accounts.authority.key(),
+            ctx.accounts.sas_authority.key(),
+            config.sas_credential,
+            attestation,
+            ctx.accounts.sas_event_authority.key(),
+            ctx.accounts.system_program.key(),
+        )?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"sas_authority",
+            &[ctx.bumps.sas_authority],
+        ]];
+
+        anchor_lang::solana_program::program::invoke_signed(
+            &close_ix,
+            &[
+                ctx.accounts.authority.to_account_info(),
+                ctx.accounts.sas_authority.to_account_info(),
+                ctx.accounts.sas_credential.to_account_info(),
+                ctx.accounts.sas_attestation.to_account_info(),
+                ctx.accounts.sas_event_authority.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        emit!(PartnerAttestationRevoked {
+            issuer: issuer.authority,
+            subject,
+            sas_schema: issuer.sas_schema,
+        });
+
+        Ok(())
+    }
+
+    /// Links another wallet to the caller's identity. Both wallets sign.
+    pub fn link_wallet(ctx: Context<LinkWallet>) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        let atom_id = &mut ctx.accounts.atom_id;
+        let wallet = ctx.accounts.wallet.key();
+
+        require!(
+            ctx.accounts.wallet_atom_id.data_is_empty(),
+            ErrorCode::WalletHasAtomId
+        );
+
+        require!(
+            (atom_id.linked_wallet_count as usize) < MAX_LINKED_WALLETS,
+            ErrorCode::TooManyLinkedWallets
+        );
+        atom_id.linked_wallet_count += 1;
+
+        let link = &mut ctx.accounts.wallet_link;
+        link.wallet = wallet;
+        link.owner = atom_id.owner;
+        link.atom_id = atom_id.key();
+        link.linked_at_slot = Clock::get()?.slot;
+        link.bump = ctx.bumps.wallet_link;
+
+        emit!(WalletLinked {
+            owner: atom_id.owner,
+            wallet,
+        });
+
+        Ok(())
+    }
+
+    /// Either the owner or the linked wallet can remove the link.
+    pub fn unlink_wallet(ctx: Context<UnlinkWallet>) -> Result<()> {
+        let link = &ctx.accounts.wallet_link;
+        let authority = ctx.accounts.authority.key();
+        require!(
+            authority == link.owner || authority == link.wallet,
+            ErrorCode::Unauthorized
+        );
+
+        let atom_id = &mut ctx.accounts.atom_id;
+        atom_id.linked_wallet_count = atom_id.linked_wallet_count.saturating_sub(1);
+
+        emit!(WalletUnlinked {
+            owner: link.owner,
+            wallet: link.wallet,
+        });
+
+        Ok(())
+    }
+
+    /// Mints a non-transferable Token-2022 badge for the caller's identity,
+    /// with rank and total burned in its on-mint metadata.
+    pub fn mint_badge(ctx: Context<MintBadge>) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        let atom_id = &ctx.accounts.atom_id;
+        atom_id.require_active()?;
+        require!(!atom_id.has_badge, ErrorCode::BadgeAlreadyMinted);
+
+        let uri = match ctx.accounts.rank_tier.as_ref() {
+            Some(tier) => {
+                require!(tier.rank == atom_id.rank, ErrorCode::RankOutOfRange);
+                tier.badge_uri.clone()
+            }
+            None => {
+                // A rank with a tier must show that tier's artwork
+                require!(
+                    ctx.accounts.atom_config.rank_tier_mask & rank_tier_bit(atom_id.rank) == 0,
+                    ErrorCode::MissingBadgeAccounts
+                );
+                String::new()
+            }
+        };
+
+        let atom_id_key = atom_id.key();
+        let mint_seeds: &[&[u8]] = &[b"badge_mint", atom_id_key.as_ref(), &[ctx.bumps.badge_mint]];
+        let authority_seeds: &[&[u8]] = &[b"badge_authority", &[ctx.bumps.badge_authority]];
+
+        create_badge_mint(
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.badge_mint.to_account_info(),
+            &ctx.accounts.badge_authority.to_account_info(),
+            &ctx.accounts.token_2022_program.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            mint_seeds,
+            authority_seeds,
+            uri,
+            atom_id.rank,
+            atom_id.total_burned,
+        )?;
+
+        associated_token::create(CpiContext::new(
+            ctx.accounts.associated_token_program.to_account_info(),
+            associated_token::Create {
+                payer: ctx.accounts.user.to_account_info(),
+                associated_token: ctx.accounts.user_badge_account.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+                mint: ctx.accounts.badge_mint.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                token_program: ctx.accounts.token_2022_program.to_account_info(),
+            },
+        ))?;
+
+        token_2022::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_2022_program.to_account_info(),
+                token_2022::MintTo {
+                    mint: ctx.accounts.badge_mint.to_account_info(),
+                    to: ctx.accounts.user_badge_account.to_account_info(),
+                    authority: ctx.accounts.badge_authority.to_account_info(),
+                },
+                &[authority_seeds],
+            ),
+            1,
+        )?;
+
+        let atom_id = &mut ctx.accounts.atom_id;
+        atom_id.has_badge = true;
+
+        emit!(BadgeMinted {
+            owner: atom_id.owner,
+            mint: ctx.accounts.badge_mint.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn update_metadata(
+        ctx: Context<UpdateMetadata>,
+        new_metadata: String,
+    ) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        require!(
+            new_metadata.len() <= 200,
+            ErrorCode::MetadataTooLong
+        );
+
+        let atom_id = &mut ctx.accounts.atom_id;
+        atom_id.require_active()?;
+        atom_id.metadata = new_metadata;
+        atom_id.updated_at_slot = Clock::get()?.slot;
+
+        let event = ReputationEvent::MetadataUpdated {
+            metadata_hash: metadata_hash(&atom_id.metadata),
+            slot: atom_id.updated_at_slot,
+        };
+        atom_id.record_reputation(event)?;
+
+        Ok(())
+    }
+
+    /// Vouches for another AtomID holder. The endorsement is weighted by the
+    /// endorser's rank at the time it is made.
+    pub fn endorse(ctx: Context<Endorse>) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        let endorser = &ctx.accounts.endorser_atom_id;
+        let endorsee = &mut ctx.accounts.endorsee_atom_id;
+        require_keys_neq!(endorser.owner, endorsee.owner, ErrorCode::InvalidEndorsee);
+        endorser.require_active()?;
+        endorsee.require_active()?;
+
+        let weight = endorser.rank as u64;
+        endorsee.endorsement_score = endorsee
+            .endorsement_score
+            .checked_add(weight)
+            .ok_or(ErrorCode::Overflow)?;
+        endorsee.endorsement_count = endorsee
+            .endorsement_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let endorsement = &mut ctx.accounts.endorsement;
+        endorsement.endorser = endorser.owner;
+        endorsement.endorsee = endorsee.owner;
+        endorsement.weight = weight;
+        endorsement.created_at_slot = Clock::get()?.slot;
+        endorsement.bump = ctx.bumps.endorsement;
+
+        let event = ReputationEvent::Endorsed {
+            endorser: endorsement.endorser,
+            weight,
+            slot: endorsement.created_at_slot,
+        };
+        endorsee.record_reputation(event)?;
+
+        emit!(EndorsementAdded {
+            endorser: endorsement.endorser,
+            endorsee: endorsement.endorsee,
+            weight,
+            endorsee_score: endorsee.endorsement_score,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraws an endorsement, removing the weight it added.
+    pub fn revoke_endorsement(ctx: Context<RevokeEndorsement>) -> Result<()> {
+        let endorsement = &ctx.accounts.endorsement;
+        let endorsee = &mut ctx.accounts.endorsee_atom_id;
+
+        endorsee.endorsement_score = endorsee.endorsement_score.saturating_sub(endorsement.weight);
+        endorsee.endorsement_count = endorsee.endorsement_count.saturating_sub(1);
+
+        let event = ReputationEvent::EndorsementRevoked {
+            endorser: endorsement.endorser,
+            weight: endorsement.weight,
+            slot: Clock::get()?.slot,
+        };
+        endorsee.record_reputation(event)?;
+
+        emit!(EndorsementRevoked {
+            endorser: endorsement.endorser,
+            endorsee: endorsement.endorsee,
+            weight: endorsement.weight,
+            endorsee_score: endorsee.endorsement_score,
+        });
+
+        Ok(())
+    }
+
+    /// Claims a unique handle for the caller's AtomID. `name` must already be
+    /// normalized (see `normalize_handle`) since it seeds the PDA.
+    pub fn claim_handle(ctx: Context<ClaimHandle>, name: String) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        validate_handle_seed(&name)?;
+
+        let atom_id = &mut ctx.accounts.atom_id;
+        atom_id.require_active()?;
+        require!(atom_id.handle.is_empty(), ErrorCode::HandleAlreadySet);
+        check_handle_rank(&ctx.accounts.atom_config, &name, atom_id.rank)?;
+
+        let handle = &mut ctx.accounts.handle;
+        handle.name = name.clone();
+        handle.owner = atom_id.owner;
+        handle.atom_id = atom_id.key();
+        handle.claimed_at_slot = Clock::get()?.slot;
+        handle.bump = ctx.bumps.handle;
+
+        atom_id.handle = name;
+
+        emit!(HandleClaimed {
+            owner: handle.owner,
+            name: handle.name.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Gives the handle up so anyone can claim it again.
+    pub fn release_handle(ctx: Context<ReleaseHandle>) -> Result<()> {
+        let atom_id = &mut ctx.accounts.atom_id;
+        atom_id.handle.clear();
+
+        emit!(HandleReleased {
+            owner: atom_id.owner,
+            name: ctx.accounts.handle.name.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Moves the handle to another AtomID, e.g. when migrating to a new
+    /// wallet. Both owners sign.
+    pub fn transfer_handle(ctx: Context<TransferHandle>) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        let handle = &mut ctx.accounts.handle;
+        let new_atom_id = &mut ctx.accounts.new_atom_id;
+
+        new_atom_id.require_active()?;
+        require!(new_atom_id.handle.is_empty(), ErrorCode::HandleAlreadySet);
+        check_handle_rank(&ctx.accounts.atom_config, &handle.name, new_atom_id.rank)?;
+
+        ctx.accounts.atom_id.handle.clear();
+        new_atom_id.handle = handle.name.clone();
+
+        handle.owner = new_atom_id.owner;
+        handle.atom_id = new_atom_id.key();
+        handle.claimed_at_slot = Clock::get()?.slot;
+
+        emit!(HandleTransferred {
+            name: handle.name.clone(),
+            from: ctx.accounts.user.key(),
+            to: handle.owner,
+        });
+
+        Ok(())
+    }
+
+    /// Founds a guild with the caller as admin and first member.
+    pub fn create_guild(ctx: Context<CreateGuild>, name: String) -> Result<()> {
+        validate_guild_name(&name)?;
+
+        let config = &ctx.accounts.atom_config;
+        config.require_not_paused()?;
+        ctx.accounts.atom_id.require_active()?;
+
+        let slot = Clock::get()?.slot;
+
+        let guild = &mut ctx.accounts.guild;
+        guild.name = name;
+        guild.admin = ctx.accounts.user.key();
+        guild.total_burned = 0;
+        guild.burn_count = 0;
+        guild.rank = config.guild_rank_for(0);
+        guild.member_count = 1;
+        guild.sas_credential = config.sas_credential;
+        guild.sas_schema = config.guild_sas_schema;
+        guild.created_at_slot = slot;
+        guild.updated_at_slot = slot;
+        guild.bump = ctx.bumps.guild;
+
+        let member = &mut ctx.accounts.guild_member;
+        member.guild = guild.key();
+        member.member = guild.admin;
+        member.contributed = 0;
+        member.joined_at_slot = slot;
+        member.bump = ctx.bumps.guild_member;
+
+        emit!(GuildCreated {
+            guild: guild.key(),
+            name: guild.name.clone(),
+            admin: guild.admin,
+        });
+
+        Ok(())
+    }
+
+    /// Admits an AtomID holder. The admin and the new member both sign.
+    pub fn add_guild_member(ctx: Context<AddGuildMember>) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        ctx.accounts.member_atom_id.require_active()?;
+
+        let guild = &mut ctx.accounts.guild;
+        guild.member_count = guild
+            .member_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let member = &mut ctx.accounts.guild_member;
+        member.guild = guild.key();
+        member.member = ctx.accounts.member.key();
+        member.contributed = 0;
+        member.joined_at_slot = Clock::get()?.slot;
+        member.bump = ctx.bumps.guild_member;
+
+        emit!(GuildMemberAdded {
+            guild: guild.key(),
+            member: member.member,
+        });
+
+        Ok(())
+    }
+
+    /// Removes a member on the admin's say. Their past contributions stay
+    /// in the guild's total.
+    pub fn remove_guild_member(ctx: Context<RemoveGuildMember>) -> Result<()> {
+        let guild = &mut ctx.accounts.guild;
+        require!(
+            ctx.accounts.guild_member.member != guild.admin,
+            ErrorCode::GuildAdminCannotLeave
+        );
+        guild.member_count = guild
+            .member_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(GuildMemberRemoved {
+            guild: guild.key(),
+            member: ctx.accounts.guild_member.member,
+        });
+
+        Ok(())
+    }
+
+    pub fn leave_guild(ctx: Context<LeaveGuild>) -> Result<()> {
+        let guild = &mut ctx.accounts.guild;
+        require!(
+            ctx.accounts.member.key() != guild.admin,
+            ErrorCode::GuildAdminCannotLeave
+        );
+        guild.member_count = guild
+            .member_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(GuildMemberRemoved {
+            guild: guild.key(),
+            member: ctx.accounts.member.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Hands the guild to another member. Both sign.
+    pub fn transfer_guild_admin(ctx: Context<TransferGuildAdmin>) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        let guild = &mut ctx.accounts.guild;
+        guild.admin = ctx.accounts.new_admin.key();
+
+        emit!(GuildAdminTransferred {
+            guild: guild.key(),
+            from: ctx.accounts.admin.key(),
+            to: guild.admin,
+        });
+
+        Ok(())
+    }
+
+    /// Permissionless: reissues the guild's attestation from its pooled
+    /// totals, with the guild PDA as nonce, under the guild schema. The rent
+    /// of the attestation it replaces goes back to whoever paid for it.
+    pub fn refresh_guild_attestation(ctx: Context<RefreshGuildAttestation>) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+        config.require_not_paused()?;
+
+        let guild = &ctx.accounts.guild;
+
+        require!(
+            config.guild_sas_schema != Pubkey::default(),
+            ErrorCode::InvalidSasSchema
+        );
+
+        require!(
+            ctx.accounts.guild_sas_credential.key() == guild.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
+
+        require!(
+            ctx.accounts.sas_credential.key() == config.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
+
+        require!(
+            ctx.accounts.sas_schema.key() == config.guild_sas_schema,
+            ErrorCode::InvalidSasSchema
+        );
+
+        require!(
+            ctx.accounts.sas_authority.key() == config.sas_authority,
+            ErrorCode::InvalidSasAuthority
+        );
+
+        let (old_attestation, _) =
+            derive_attestation_pda(&guild.sas_credential, &guild.sas_schema, &guild.key());
+        require_keys_eq!(
+            ctx.accounts.old_sas_attestation.key(),
+            old_attestation,
+            ErrorCode::InvalidAttestationAddress
+        );
+
+        let (new_attestation, _) =
+            derive_attestation_pda(&config.sas_credential, &config.guild_sas_schema, &guild.key());
+        require_keys_eq!(
+            ctx.accounts.new_sas_attestation.key(),
+            new_attestation,
+            ErrorCode::InvalidAttestationAddress
+        );
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"sas_authority",
+            &[ctx.bumps.sas_authority],
+        ]];
+
+        if attestation_exists(&ctx.accounts.old_sas_attestation) {
+            let previous_payer = ctx
+                .accounts
+                .previous_payer
+                .as_ref()
+                .ok_or(ErrorCode::Unauthorized)?;
+            require_keys_eq!(
+                previous_payer.key(),
+                guild.attestation_payer,
+                ErrorCode::Unauthorized
+            );
+
+            let payer_lamports = ctx.accounts.payer.lamports();
+
+            let close_ix = close_attestation_instruction(
+                ctx.accounts.payer.key(),
+                ctx.accounts.sas_authority.key(),
+                guild.sas_credential,
+                ctx.accounts.old_sas_attestation.key(),
+                ctx.accounts.sas_event_authority.key(),
+                ctx.accounts.system_program.key(),
+            )?;
+
+            anchor_lang::solana_program::program::invoke_signed(
+                &close_ix,
+                &[
+                    ctx.accounts.payer.to_account_info(),
+                    ctx.accounts.sas_authority.to_account_info(),
+                    ctx.accounts.guild_sas_credential.to_account_info(),
+                    ctx.accounts.old_sas_attestation.to_account_info(),
+                    ctx.accounts.sas_event_authority.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                    ctx.accounts.sas_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+
+            // SAS refunds the closing payer; pass that on to whoever paid
+            // for the attestation being replaced
+            let refund = ctx.accounts.payer.lamports().saturating_sub(payer_lamports);
+            if refund > 0 && previous_payer.key() != ctx.accounts.payer.key() {
+                anchor_lang::system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        anchor_lang::system_program::Transfer {
+                            from: ctx.accounts.payer.to_account_info(),
+                            to: previous_payer.to_account_info(),
+                        },
+                    ),
+                    refund,
+                )?;
+            }
+        }
+
+        let attestation_data = serialize_guild_attestation_data(guild);
+
+        let expiry_timestamp = Clock::get()?.unix_timestamp + ATTESTATION_VALIDITY_SECONDS;
+
+        let attestation_ix = create_attestation_instruction(
+            ctx.accounts.payer.key(),
+            ctx.accounts.sas_authority.key(),
+            config.sas_credential,
+            config.guild_sas_schema,
+            ctx.accounts.new_sas_attestation.key(),
+            ctx.accounts.system_program.key(),
+            guild.key(),
+            attestation_data,
+            expiry_timestamp,
+        )?;
+
+        anchor_lang::solana_program::program::invoke_signed(
+            &attestation_ix,
+            &[
+                ctx.accounts.payer.to_account_info(),
+                ctx.accounts.sas_authority.to_account_info(),
+                ctx.accounts.sas_credential.to_account_info(),
+                ctx.accounts.sas_schema.to_account_info(),
+                ctx.accounts.new_sas_attestation.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        emit!(GuildAttestationRefreshed {
+            guild: guild.key(),
+            rank: guild.rank,
+            total_burned: guild.total_burned,
+        });
+
+        let guild = &mut ctx.accounts.guild;
+        guild.sas_credential = config.sas_credential;
+        guild.sas_schema = config.guild_sas_schema;
+        guild.attestation_payer = ctx.accounts.payer.key();
+
+        Ok(())
+    }
+
+    /// Creates the caller's structured profile, sized to its contents.
+    pub fn create_profile(ctx: Context<CreateProfile>, fields: ProfileFields) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        fields.validate()?;
+
+        let profile = &mut ctx.accounts.profile;
+        profile.owner = ctx.accounts.user.key();
+        profile.fields = fields;
+        profile.updated_at_slot = Clock::get()?.slot;
+        profile.bump = ctx.bumps.profile;
+
+        emit!(ProfileUpdated {
+            owner: profile.owner,
+        });
+
+        Ok(())
+    }
+
+    /// Replaces the profile contents, growing or shrinking the account to
+    /// fit (up to `MAX_PROFILE_SPACE`).
+    pub fn update_profile(ctx: Context<UpdateProfile>, fields: ProfileFields) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        fields.validate()?;
+
+        let profile = &mut ctx.accounts.profile;
+        profile.fields = fields;
+        profile.updated_at_slot = Clock::get()?.slot;
+
+        emit!(ProfileUpdated {
+            owner: profile.owner,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_profile(ctx: Context<CloseProfile>) -> Result<()> {
+        emit!(ProfileClosed {
+            owner: ctx.accounts.profile.owner,
+        });
+
+        Ok(())
+    }
+
+    /// Sets how AtomIDs translate to voting power in `realm`. Only the
+    /// realm's authority can configure it.
+    pub fn configure_realm_voter(
+        ctx: Context<ConfigureRealmVoter>,
+        governance_program: Pubkey,
+        governing_token_mint: Pubkey,
+        formula: VoterWeightFormula,
+        max_voter_weight: u64,
+    ) -> Result<()> {
+        let realm = &ctx.accounts.realm;
+        require!(
+            realm_authority(realm, &governance_program)? == Some(ctx.accounts.realm_authority.key()),
+            ErrorCode::Unauthorized
+        );
+
+        let voter = &mut ctx.accounts.realm_voter;
+        voter.realm = realm.key();
+        voter.governance_program = governance_program;
+        voter.governing_token_mint = governing_token_mint;
+        voter.formula = formula;
+        voter.max_voter_weight = max_voter_weight;
+        voter.bump = ctx.bumps.realm_voter;
+
+        emit!(RealmVoterConfigured {
+            realm: voter.realm,
+            governing_token_mint,
+            formula,
+            max_voter_weight,
+        });
+
+        Ok(())
+    }
+
+    pub fn update_realm_voter(
+        ctx: Context<UpdateRealmVoter>,
+        formula: VoterWeightFormula,
+        max_voter_weight: u64,
+    ) -> Result<()> {
+        let voter = &mut ctx.accounts.realm_voter;
+        require!(
+            realm_authority(&ctx.accounts.realm, &voter.governance_program)?
+                == Some(ctx.accounts.realm_authority.key()),
+            ErrorCode::Unauthorized
+        );
+
+        voter.formula = formula;
+        voter.max_voter_weight = max_voter_weight;
+
+        emit!(RealmVoterConfigured {
+            realm: voter.realm,
+            governing_token_mint: voter.governing_token_mint,
+            formula,
+            max_voter_weight,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_voter_weight_record(ctx: Context<CreateVoterWeightRecord>) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        let voter = &ctx.accounts.realm_voter;
+        let record = &mut ctx.accounts.voter_weight_record;
+        record.realm = voter.realm;
+        record.governing_token_mint = voter.governing_token_mint;
+        record.governing_token_owner = ctx.accounts.user.key();
+        record.voter_weight = 0;
+        record.voter_weight_expiry = Some(0);
+        record.weight_action = None;
+        record.weight_action_target = None;
+
+        Ok(())
+    }
+
+    /// Refreshes the caller's voter weight from their AtomID. Governance
+    /// only accepts it in the same slot, so clients prepend this to the
+    /// action's transaction.
+    pub fn update_voter_weight_record(
+        ctx: Context<UpdateVoterWeightRecord>,
+        action: VoterWeightAction,
+        action_target: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.atom_config.require_not_paused()?;
+
+        let atom_id = &ctx.accounts.atom_id;
+        atom_id.require_active()?;
+
+        let record = &mut ctx.accounts.voter_weight_record;
+        record.voter_weight = ctx.accounts.realm_voter.formula.weight(atom_id)?;
+        record.voter_weight_expiry = Some(Clock::get()?.slot);
+        record.weight_action = Some(action);
+        record.weight_action_target = action_target;
+
+        Ok(())
+    }
+
+    pub fn create_max_voter_weight_record(ctx: Context<CreateMaxVoterWeightRecord>) -> Result<()> {
+        let voter = &ctx.accounts.realm_voter;
+        let record = &mut ctx.accounts.max_voter_weight_record;
+        record.realm = voter.realm;
+        record.governing_token_mint = voter.governing_token_mint;
+        record.max_voter_weight = voter.max_voter_weight;
+        record.max_voter_weight_expiry = None;
+
+        Ok(())
+    }
+
+    /// Permissionless; copies the realm's configured maximum.
+    pub fn update_max_voter_weight_record(ctx: Context<UpdateMaxVoterWeightRecord>) -> Result<()> {
+        ctx.accounts.max_voter_weight_record.max_voter_weight =
+            ctx.accounts.realm_voter.max_voter_weight;
+
+        Ok(())
+    }
+
+    pub fn initialize_rank_hooks(ctx: Context<InitializeRankHooks>) -> Result<()> {
+        ctx.accounts.rank_hooks.bump = ctx.bumps.rank_hooks;
+        Ok(())
+    }
+
+    /// Subscribes a program to rank changes. Its `on_rank_changed` runs
+    /// inside every instruction that sets a new rank, so only vetted programs
+    /// should be registered.
+    pub fn register_rank_hook(ctx: Context<UpdateRankHooks>, hook: RankHook) -> Result<()> {
+        let registry = &mut ctx.accounts.rank_hooks;
+
+        require!(
+            hook.compute_units > 0 && hook.compute_units <= MAX_HOOK_COMPUTE_UNITS,
+            ErrorCode::InvalidRankHook
+        );
+        require!(
+            !registry.hooks.iter().any(|h| h.program_id == hook.program_id),
+            ErrorCode::InvalidRankHook
+        );
+        require!(
+            registry.hooks.len() < MAX_RANK_HOOKS,
+            ErrorCode::TooManyRankHooks
+        );
+
+        emit!(RankHookRegistered {
+            program_id: hook.program_id,
+            compute_units: hook.compute_units,
+            failure_mode: hook.failure_mode,
+        });
+
+        registry.hooks.push(hook);
+        ctx.accounts.atom_config.rank_hook_count = registry.hooks.len() as u8;
+
+        Ok(())
+    }
+
+    pub fn remove_rank_hook(ctx: Context<UpdateRankHooks>, program_id: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.rank_hooks;
+        let index = registry
+            .hooks
+            .iter()
+            .position(|h| h.program_id == program_id)
+            .ok_or(ErrorCode::InvalidRankHook)?;
+        registry.hooks.remove(index);
+        ctx.accounts.atom_config.rank_hook_count = registry.hooks.len() as u8;
+
+        emit!(RankHookRemoved { program_id });
+
+        Ok(())
+    }
+
+    /// Creates the tree holding compressed AtomIDs.
+    pub fn initialize_identity_tree(
+        ctx: Context<InitializeIdentityTree>,
+        depth: u8,
+    ) -> Result<()> {
+        let tree = &mut ctx.accounts.identity_tree;
+        tree.initialize(depth)?;
+        tree.bump = ctx.bumps.identity_tree;
+
+        emit!(IdentityTreeInitialized {
+            depth,
+            root: tree.root(),
+        });
+
+        Ok(())
+    }
+
+    /// Creates an AtomID as a leaf of the identity tree rather than a full
+    /// account. No attestation is issued until it is promoted; the leaf data
+    /// itself is only available from the emitted event.
+    pub fn create_compressed_atomid(
+        ctx: Context<CreateCompressedAtomId>,
+        burn_amount: u64,
+    ) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+
+        config.require_not_paused()?;
+
+        require!(
+            ctx.accounts.atom_id.data_is_empty(),
+            ErrorCode::WalletHasAtomId
+        );
+
+        require!(
+            ctx.accounts.wallet_link.data_is_empty(),
+            ErrorCode::WalletAlreadyLinked
+        );
+
+        require!(
+            ctx.accounts.atom_mint.key() == config.burn_mint,
+            ErrorCode::InvalidBurnMint
+        );
+
+        require!(
+            burn_amount >= config.min_create_burn,
+            ErrorCode::InsufficientBurnAmount
+        );
+
+        token::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.atom_mint.to_account_info(),
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            burn_amount,
+        )?;
+
+        let current_slot = Clock::get()?.slot;
+        let leaf = CompressedAtomId {
+            owner: ctx.accounts.user.key(),
+            total_burned: burn_amount,
+            rank: config.rank_for(burn_amount),
+            created_at_slot: current_slot,
+            updated_at_slot: current_slot,
+            burn_count: 1,
+        };
+
+        let tree = &mut ctx.accounts.identity_tree;
+        let leaf_index = tree.append(leaf.leaf_hash())?;
+
+        let marker = &mut ctx.accounts.compressed_atomid;
+        marker.owner = leaf.owner;
+        marker.leaf_index = leaf_index;
+        marker.bump = ctx.bumps.compressed_atomid;
+
+        emit!(CompressedAtomIdUpdated {
+            leaf_index,
+            atom_id: leaf,
+            root: tree.root(),
+            sequence: tree.sequence,
+        });
+
+        Ok(())
+    }
+
+    /// Burns more into a compressed AtomID. `leaf` is its current data and
+    /// `proof` the sibling hashes against `root`.
+    pub fn upgrade_compressed_atomid(
+        ctx: Context<UpgradeCompressedAtomId>,
+        burn_amount: u64,
+        root: [u8; 32],
+        leaf: CompressedAtomId,
+        leaf_index: u32,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+
+        config.require_not_paused()?;
+        require!(leaf.owner == ctx.accounts.user.key(), ErrorCode::Unauthorized);
+
+        require!(
+            ctx.accounts.atom_mint.key() == config.burn_mint,
+            ErrorCode::InvalidBurnMint
+        );
+
+        require!(burn_amount > 0, ErrorCode::InsufficientBurnAmount);
+
+        token::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.atom_mint.to_account_info(),
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            burn_amount,
+        )?;
+
+        let mut upgraded = leaf.clone();
+        upgraded.total_burned = leaf
+            .total_burned
+            .checked_add(burn_amount)
+            .ok_or(ErrorCode::Overflow)?;
+        upgraded.burn_count = leaf
+            .burn_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+        upgraded.rank = config.rank_for(upgraded.total_burned);
+        upgraded.updated_at_slot = Clock::get()?.slot;
+
+        let tree = &mut ctx.accounts.identity_tree;
+        tree.replace(
+            root,
+            leaf.leaf_hash(),
+            upgraded.leaf_hash(),
+            leaf_index,
+            &proof,
+        )?;
+
+        emit!(CompressedAtomIdUpdated {
+            leaf_index,
+            atom_id: upgraded,
+            root: tree.root(),
+            sequence: tree.sequence,
+        });
+
+        Ok(())
+    }
+
+    /// Turns a compressed AtomID into a full `AtomId` account with an
+    /// attestation, clearing its leaf.
+    pub fn promote_compressed_atomid<'info>(
+        ctx: Context<'_, '_, '_, 'info, PromoteCompressedAtomId<'info>>,
+        root: [u8; 32],
+        leaf: CompressedAtomId,
+        leaf_index: u32,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+
+        config.require_not_paused()?;
+        require!(leaf.owner == ctx.accounts.user.key(), ErrorCode::Unauthorized);
+        require!(
+            leaf_index == ctx.accounts.compressed_atomid.leaf_index,
+            ErrorCode::InvalidMerkleProof
+        );
+
+        require!(
+            ctx.accounts.wallet_link.data_is_empty(),
+            ErrorCode::WalletAlreadyLinked
+        );
+
+        // A linked attestation already sits at the attestation address
+        require!(
+            ctx.accounts.linked_attestation.data_is_empty(),
+            ErrorCode::WalletHasLinkedAttestation
+        );
+
+        require!(
+            ctx.accounts.sas_credential.key() == config.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
+
+        require!(
+            ctx.accounts.sas_schema.key() == config.sas_schema,
+            ErrorCode::InvalidSasSchema
+        );
+
+        require!(
+            ctx.accounts.sas_authority.key() == config.sas_authority,
+            ErrorCode::InvalidSasAuthority
+        );
+
+        let tree = &mut ctx.accounts.identity_tree;
+        tree.replace(root, leaf.leaf_hash(), EMPTY_LEAF, leaf_index, &proof)?;
+
+        let atom_id = &mut ctx.accounts.atom_id;
+
+        atom_id.owner = leaf.owner;
+        atom_id.total_burned = leaf.total_burned;
+        atom_id.rank = config.rank_for(leaf.total_burned);
+        atom_id.created_at_slot = leaf.created_at_slot;
+        atom_id.updated_at_slot = Clock::get()?.slot;
+        atom_id.burn_count = leaf.burn_count;
+        atom_id.bump = ctx.bumps.atom_id;
+        atom_id.layout_version = ATOM_ID_LAYOUT_VERSION;
+
+        let event = ReputationEvent::Created {
+            total_burned: atom_id.total_burned,
+            rank: atom_id.rank,
+            metadata_hash: metadata_hash(&atom_id.metadata),
+            slot: atom_id.updated_at_slot,
+        };
+        atom_id.record_reputation(event)?;
+
+        let attestation_data = serialize_atomid_attestation_data(config.sas_schema_version, atom_id)?;
+
+        let expiry_timestamp = Clock::get()?.unix_timestamp + ATTESTATION_VALIDITY_SECONDS;
+
+        let attestation_ix = create_attestation_instruction(
+            ctx.accounts.user.key(),
+            ctx.accounts.sas_authority.key(),
+            config.sas_credential,
+            config.sas_schema,
+            ctx.accounts.sas_attestation.key(),
+            ctx.accounts.system_program.key(),
+            ctx.accounts.user.key(),
+            attestation_data,
+            expiry_timestamp,
+        )?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"sas_authority",
+            &[ctx.bumps.sas_authority],
+        ]];
+
+        anchor_lang::solana_program::program::invoke_signed(
+            &attestation_ix,
+            &[
+                ctx.accounts.user.to_account_info(),
+                ctx.accounts.sas_authority.to_account_info(),
+                ctx.accounts.sas_credential.to_account_info(),
+                ctx.accounts.sas_schema.to_account_info(),
+                ctx.accounts.sas_attestation.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.sas_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        emit!(CompressedAtomIdPromoted {
+            owner: atom_id.owner,
+            leaf_index,
+            root: tree.root(),
+            sequence: tree.sequence,
+        });
+
+        emit!(AtomIdCreated {
+            owner: atom_id.owner,
+            total_burned: atom_id.total_burned,
+            rank: atom_id.rank,
+        });
+
+        // Hooks may read the AtomID, so write it out first. The rank
+        // is recomputed under the current curve, which may move it
+        ctx.accounts.atom_id.exit(&crate::ID)?;
+        notify_rank_changed(
+            config,
+            ctx.accounts.rank_hooks.as_ref().map(|hooks| hooks.as_ref()),
+            ctx.accounts
+                .hook_authority
+                .as_ref()
+                .map(|authority| authority.as_ref())
+                .zip(ctx.bumps.hook_authority),
+            ctx.remaining_accounts,
+            &ctx.accounts.atom_id.to_account_info(),
+            ctx.accounts.atom_id.owner,
+            leaf.rank,
+            ctx.accounts.atom_id.rank,
+        )?;
+
+        Ok(())
+    }
+
+    /// Brings a config account created by an older program version up to
+    /// the current layout, growing it and filling new fields with the
+    /// defaults `initialize` uses. Admin only; run once after upgrading.
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        migrate_config_account(
+            &ctx.accounts.atom_config.to_account_info(),
+            &ctx.accounts.admin.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
+    }
+
+    /// Permissionless: brings an AtomID created by an older program version
+    /// up to the current layout, with `payer` covering the extra rent.
+    pub fn migrate_atomid(ctx: Context<MigrateAtomId>) -> Result<()> {
+        migrate_atom_id_account(
+            &ctx.accounts.atom_id.to_account_info(),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
+    }
+
+    pub fn admin_update_config(
+        ctx: Context<AdminUpdateConfig>,
+        change: ConfigChange,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.atom_config;
+        require!(config.config_timelock == 0, ErrorCode::TimelockActive);
+
+        change.authorize(config, &ctx.accounts.authority.key())?;
+        change.validate(config, &ctx.accounts.targets)?;
+        let previous = config.apply_change(&change);
+
+        emit!(ConfigChangeExecuted {
+            proposer: ctx.accounts.authority.key(),
+            previous,
+            change,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_role(ctx: Context<SetRole>, role: Role, holder: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.atom_config;
+        let previous = config.role(role);
+        config.set_role(role, holder);
+
+        emit!(RoleUpdated {
+            role,
+            previous,
+            holder,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        ctx.accounts.atom_config.paused = paused;
+
+        emit!(PausedUpdated {
+            paused,
+            pauser: ctx.accounts.pauser.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Suspends an identity and closes its attestations so SAS verification
+    /// fails until it is unsuspended: the current one, the one under the
+    /// previous schema, and every linked attestation, passed in
+    /// `remaining_accounts` as (`LinkedAttestation`, SAS attestation,
+    /// credential) triples. The rent goes back to the owner.
+    pub fn suspend_atomid<'info>(
+        ctx: Context<'_, '_, '_, 'info, SuspendAtomId<'info>>,
+        reason: SuspensionReason,
+    ) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+        let atom_id = &ctx.accounts.atom_id;
+        require!(atom_id.is_active(), ErrorCode::IdentitySuspended);
+
+        require!(
+            ctx.accounts.sas_credential.key() == config.sas_credential,
+            ErrorCode::InvalidSasCredential
+        );
+
+        require!(
+            ctx.accounts.sas_authority.key() == config.sas_authority,
+            ErrorCode::InvalidSasAuthority
+        );
+
+        let (attestation, _) =
+            derive_attestation_pda(&config.sas_credential, &config.sas_schema, &atom_id.owner);
+        require_keys_eq!(
+            ctx.accounts.sas_attestation.key(),
+            attestation,
+            ErrorCode::InvalidAttestationAddress
+        );
+
+        // (credential, attestation) pairs to close
+        let mut to_close = vec![(
+            ctx.accounts.sas_credential.to_account_info(),
+            ctx.accounts.sas_attestation.to_account_info(),
+        )];
+
+        if config.previous_sas_schema != Pubkey::default() {
+            let (Some(previous_credential), Some(previous_attestation)) = (
+                ctx.accounts.previous_sas_credential.as_ref(),
+                ctx.accounts.previous_sas_attestation.as_ref(),
+            ) else {
+                return err!(ErrorCode::PreviousAttestationRequired);
+            };
+
+            require!(
+                previous_credential.key() == config.previous_sas_credential,
+                ErrorCode::InvalidSasCredential
+            );
+
+            let (previous, _) = derive_attestation_pda(
+                &config.previous_sas_credential,
+                &config.previous_sas_schema,
+                &atom_id.owner,
+            );
+            require_keys_eq!(
+                previous_attestation.key(),
+                previous,
+                ErrorCode::InvalidAttestationAddress
+            );
+
+            to_close.push((
+                previous_credential.to_account_info(),
+                previous_attestation.to_account_info(),
+            ));
+        }
+
+        require!(
+            ctx.remaining_accounts.len() == 3 * atom_id.linked_attestation_count as usize,
+            ErrorCode::LinkedAttestationsRequired
+        );
+        let mut recipients = Vec::with_capacity(atom_id.linked_attestation_count as usize);
+        for accounts in ctx.remaining_accounts.chunks(3) {
+            let [linked_info, attestation_info, credential_info] = accounts else {
+                unreachable!()
+            };
+            require_keys_eq!(
+                *linked_info.owner,
+                crate::ID,
+                anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram
+            );
+            let linked =
+                LinkedAttestation::try_deserialize(&mut &linked_info.try_borrow_data()?[..])?;
+            require_keys_eq!(linked.owner, atom_id.owner, ErrorCode::LinkedAttestationsRequired);
+            require!(
+                !recipients.contains(&linked.recipient),
+                ErrorCode::LinkedAttestationsRequired
+            );
+            recipients.push(linked.recipient);
+
+            require_keys_eq!(
+                *credential_info.owner,
+                SAS_PROGRAM_ID,
+                ErrorCode::InvalidSasProgram
+            );
+            require_keys_eq!(
+                credential_info.key(),
+                linked.sas_credential,
+                ErrorCode::InvalidSasCredential
+            );
+
+            let (linked_attestation, _) =
+                derive_attestation_pda(&linked.sas_credential, &linked.sas_schema, &linked.recipient);
+            require_keys_eq!(
+                attestation_info.key(),
+                linked_attestation,
+                ErrorCode::InvalidAttestationAddress
+            );
+
+            to_close.push((credential_info.clone(), attestation_info.clone()));
+        }
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"sas_authority",
+            &[ctx.bumps.sas_authority],
+        ]];
+
+        // SAS refunds the closing payer; pass everything on to the owner
+        let balance_before = ctx.accounts.moderator.lamports();
+
+        for (credential, attestation) in to_close {
+            if !attestation_exists(&attestation) {
+                continue;
+            }
+
+            let close_ix = close_attestation_instruction(
+                ctx.accounts.moderator.key(),
+                ctx.accounts.sas_authority.key(),
+                credential.key(),
+                attestation.key(),
+                ctx.accounts.sas_event_authority.key(),
+                ctx.accounts.system_program.key(),
+            )?;
+
+            anchor_lang::solana_program::program::invoke_signed(
+                &close_ix,
+                &[
+                    ctx.accounts.moderator.to_account_info(),
+                    ctx.accounts.sas_authority.to_account_info(),
+                    credential,
+                    attestation,
+                    ctx.accounts.sas_event_authority.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                    ctx.accounts.sas_program.to_account_info(),
+                ],
+                signer_seeds,
+            )?;
+        }
+
+        let refund = ctx.accounts.moderator.lamports().saturating_sub(balance_before);
+        if refund > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.moderator.to_account_info(),
+                        to: ctx.accounts.owner.to_account_info(),
+                    },
+                ),
+                refund,
+            )?;
+        }
+
+        let suspended_at = Clock::get()?.unix_timestamp;
+        let atom_id = &mut ctx.accounts.atom_id;
+        atom_id.status = IdentityStatus::Suspended {
+            reason,
+            suspended_at,
+            appealed_at: None,
+        };
+
+        emit!(AtomIdSuspended {
+            owner: atom_id.owner,
+            reason,
+            suspended_at,
+            moderator: ctx.accounts.moderator.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Lets a suspended owner flag the suspension for review, once.
+    pub fn appeal_suspension(ctx: Context<AppealSuspension>) -> Result<()> {
+        let atom_id = &mut ctx.accounts.atom_id;
+        let IdentityStatus::Suspended {
+            ref mut appealed_at,
+            ..
+        } = atom_id.status
+        else {
+            return err!(ErrorCode::IdentityNotSuspended);
+        };
+        require!(appealed_at.is_none(), ErrorCode::AlreadyAppealed);
+
+        let now = Clock::get()?.unix_timestamp;
+        *appealed_at = Some(now);
+
+        emit!(SuspensionAppealed {
+            owner: atom_id.owner,
+            appealed_at: now,
+        });
+
+        Ok(())
+    }
+
+    /// Lifts a suspension. The owner renews the attestation with
+    /// `upgrade_atomid`.
+    pub fn unsuspend_atomid(ctx: Context<UnsuspendAtomId>) -> Result<()> {
+        let atom_id = &mut ctx.accounts.atom_id;
+        require!(!atom_id.is_active(), ErrorCode::IdentityNotSuspended);
+
+        atom_id.status = IdentityStatus::Active;
+
+        emit!(AtomIdUnsuspended {
+            owner: atom_id.owner,
+            moderator: ctx.accounts.moderator.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn queue_config_change(
+        ctx: Context<QueueConfigChange>,
+        change: ConfigChange,
+    ) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+        change.authorize(config, &ctx.accounts.authority.key())?;
+        change.validate(config, &ctx.accounts.targets)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let execute_after = now
+            .checked_add(ctx.accounts.atom_config.config_timelock)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let pending = &mut ctx.accounts.pending_config_change;
+        pending.proposer = ctx.accounts.authority.key();
+        pending.change = change.clone();
+        pending.queued_at = now;
+        pending.execute_after = execute_after;
+        pending.bump = ctx.bumps.pending_config_change;
+
+        emit!(ConfigChangeQueued {
+            proposer: pending.proposer,
+            change,
+            execute_after,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_config_change(ctx: Context<ExecuteConfigChange>) -> Result<()> {
+        let pending = &ctx.accounts.pending_config_change;
+        require!(
+            Clock::get()?.unix_timestamp >= pending.execute_after,
+            ErrorCode::TimelockNotElapsed
+        );
+
+        // Anyone can execute, so the proposer must still hold the roles the
+        // change needs
+        let change = pending.change.clone();
+        change.authorize(&ctx.accounts.atom_config, &pending.proposer)?;
+        change.validate(&ctx.accounts.atom_config, &ctx.accounts.targets)?;
+        let previous = ctx.accounts.atom_config.apply_change(&change);
+
+        emit!(ConfigChangeExecuted {
+            proposer: pending.proposer,
+            previous,
+            change,
+        });
+
+        Ok(())
+    }
+
+    /// The admin can cancel any change; otherwise the caller needs the roles
+    /// the change would need.
+    pub fn cancel_config_change(ctx: Context<CancelConfigChange>) -> Result<()> {
+        let config = &ctx.accounts.atom_config;
+        let authority = ctx.accounts.authority.key();
+        let change = ctx.accounts.pending_config_change.change.clone();
+        if authority != config.admin {
+            change.authorize(config, &authority)?;
+        }
+
+        emit!(ConfigChangeCancelled {
+            proposer: ctx.accounts.proposer.key(),
+            change,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_rank_tier(
+        ctx: Context<CreateRankTier>,
+        rank: u8,
+        name: String,
+        badge_uri: String,
+        perks: u64,
+    ) -> Result<()> {
+        // Tiers for ranks the current curve doesn't reach yet may be set up
+        // ahead of a config change; coverage is checked when it executes
+        require!(rank as usize <= MAX_RANK_TIERS, ErrorCode::RankOutOfRange);
+        validate_rank_tier(&name, &badge_uri)?;
+
+        let config = &mut ctx.accounts.atom_config;
+        config.rank_tier_mask |= rank_tier_bit(rank);
+
+        let rank_tier = &mut ctx.accounts.rank_tier;
+        rank_tier.rank = rank;
+        rank_tier.name = name;
+        rank_tier.badge_uri = badge_uri;
+        rank_tier.perks = perks;
+        rank_tier.bump = ctx.bumps.rank_tier;
+
+        emit!(RankTierUpdated {
+            rank,
+            name: rank_tier.name.clone(),
+            badge_uri: rank_tier.badge_uri.clone(),
+            perks,
+        });
+
+        Ok(())
+    }
+
+    pub fn update_rank_tier(
+        ctx: Context<UpdateRankTier>,
+        name: Option<String>,
+        badge_uri: Option<String>,
+        perks: Option<u64>,
+    ) -> Result<()> {
+        let rank_tier = &mut ctx.accounts.rank_tier;
+
+        if let Some(name) = name {
+            rank_tier.name = name;
+        }
+
+        if let Some(badge_uri) = badge_uri {
+            rank_tier.badge_uri = badge_uri;
+        }
+
+        if let Some(perks) = perks {
+            rank_tier.perks = perks;
+        }
+
+        validate_rank_tier(&rank_tier.name, &rank_tier.badge_uri)?;
+
+        emit!(RankTierUpdated {
+            rank: rank_tier.rank,
+            name: rank_tier.name.clone(),
+            badge_uri: rank_tier.badge_uri.clone(),
+            perks: rank_tier.perks,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_rank_tier(ctx: Context<CloseRankTier>) -> Result<()> {
+        let config = &mut ctx.accounts.atom_config;
+        config.rank_tier_mask &= !rank_tier_bit(ctx.accounts.rank_tier.rank);
+
+        // Ranks the current curve reaches keep their tiers
+        validate_tier_coverage(
+            config.rank_tier_mask,
+            config.rank_curve.tier_count(&config.rank_thresholds),
+        )?;
+
+        emit!(RankTierClosed {
+            rank: ctx.accounts.rank_tier.rank,
+        });
+
+        Ok(())
+    }
+
+    /// Print security.txt to program logs for verification
+    pub fn print_security_txt(_ctx: Context<PrintSecurityTxt>) -> Result<()> {
+        let security_txt = include_str!("security.txt");
+        msg!("security_txt: {}", security_txt);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct PrintSecurityTxt {}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + AtomConfig::INIT_SPACE,
+        seeds = [b"atomid_config"],
+        bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeSasCredential<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    /// CHECK: PDA used to sign SAS operations
+    pub sas_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Created by SAS program
+    #[account(mut)]
+    pub sas_credential: UncheckedAccount<'info>,
+
+    /// CHECK: SAS program, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeSasSchema<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    /// CHECK: PDA used to sign SAS operations
+    pub sas_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Created by SAS program
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: UncheckedAccount<'info>,
+
+    /// CHECK: Created by SAS program
+    #[account(mut)]
+    pub sas_schema: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: SAS program for CPI, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateAtomId<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + AtomId::INIT_SPACE,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: User's wallet link PDA, must not exist
+    #[account(
+        seeds = [b"wallet_link", user.key().as_ref()],
+        bump
+    )]
+    pub wallet_link: UncheckedAccount<'info>,
+
+    /// CHECK: User's compressed AtomID marker PDA, must not exist
+    #[account(
+        seeds = [b"compressed_atomid", user.key().as_ref()],
+        bump
+    )]
+    pub compressed_atomid: UncheckedAccount<'info>,
+
+    /// CHECK: Linked attestation PDA naming this wallet as recipient, must
+    /// not exist
+    #[account(
+        seeds = [b"linked_attestation", user.key().as_ref()],
+        bump
+    )]
+    pub linked_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: Validated in instruction handler
+    #[account(mut)]
+    pub user_token_account: AccountInfo<'info>,
+
+    /// CHECK: Validated in instruction handler
+    #[account(mut)]
+    pub atom_mint: AccountInfo<'info>,
+
+    /// CHECK: SAS attestation PDA - will be created by SAS program via CPI
+    #[account(mut)]
+    pub sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS schema account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_schema: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: AtomID of `referrer`, required when one is given; loaded with
+    /// `load_atom_id`, which checks it and migrates an old layout
+    #[account(mut)]
+    pub referrer_atom_id: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Rank hook registry PDA, required once hooks are registered
+    #[account(
+        seeds = [b"rank_hooks"],
+        bump
+    )]
+    pub rank_hooks: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Signer PDA hooks are called with, required when hooks are registered
+    #[account(
+        seeds = [b"hook_authority"],
+        bump
+    )]
+    pub hook_authority: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateOrganizationAtomId<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + AtomId::INIT_SPACE,
+        seeds = [b"atomid", owner.key().as_ref()],
+        bump
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// Program-derived owner, signing through CPI
+    pub owner: Signer<'info>,
+
+    /// CHECK: Owner's wallet link PDA, must not exist
+    #[account(
+        seeds = [b"wallet_link", owner.key().as_ref()],
+        bump
+    )]
+    pub wallet_link: UncheckedAccount<'info>,
+
+    /// CHECK: Owner's compressed AtomID marker PDA, must not exist
+    #[account(
+        seeds = [b"compressed_atomid", owner.key().as_ref()],
+        bump
+    )]
+    pub compressed_atomid: UncheckedAccount<'info>,
+
+    /// CHECK: Linked attestation PDA naming this wallet as recipient, must
+    /// not exist
+    #[account(
+        seeds = [b"linked_attestation", owner.key().as_ref()],
+        bump
+    )]
+    pub linked_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: Validated in instruction handler
+    #[account(mut)]
+    pub owner_token_account: AccountInfo<'info>,
+
+    /// CHECK: Validated in instruction handler
+    #[account(mut)]
+    pub atom_mint: AccountInfo<'info>,
+
+    /// CHECK: SAS attestation PDA - will be created by SAS program via CPI
+    #[account(mut)]
+    pub sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS schema account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_schema: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Rank hook registry PDA, required once hooks are registered
+    #[account(
+        seeds = [b"rank_hooks"],
+        bump
+    )]
+    pub rank_hooks: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Signer PDA hooks are called with, required when hooks are registered
+    #[account(
+        seeds = [b"hook_authority"],
+        bump
+    )]
+    pub hook_authority: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct UpgradeAtomId<'info> {
+    /// CHECK: Caller's AtomID, loaded with `load_atom_id` so accounts in an
+    /// older layout are migrated first
+    #[account(
+        mut,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump
+    )]
+    pub atom_id: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: Validated in instruction handler
+    #[account(mut)]
+    pub user_token_account: AccountInfo<'info>,
+
+    /// CHECK: Validated in instruction handler
+    #[account(mut)]
+    pub atom_mint: AccountInfo<'info>,
+
+    /// CHECK: Caller's attestation under the current schema, closed and
+    /// reissued via CPI; verified against its PDA
+    #[account(mut)]
+    pub sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS schema account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_schema: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS event authority PDA
+    #[account(
+        seeds = [b"__event_authority"],
+        bump,
+        seeds::program = SAS_PROGRAM_ID
+    )]
+    pub sas_event_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Soulbound badge mint, required once the AtomID has a badge
+    #[account(
+        mut,
+        seeds = [b"badge_mint", atom_id.key().as_ref()],
+        bump
+    )]
+    pub badge_mint: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Badge mint and metadata authority PDA
+    #[account(
+        seeds = [b"badge_authority"],
+        bump
+    )]
+    pub badge_authority: Option<UncheckedAccount<'info>>,
+
+    pub token_2022_program: Option<Program<'info, Token2022>>,
+
+    /// CHECK: Rank hook registry PDA, required once hooks are registered;
+    /// may not exist
+    #[account(
+        seeds = [b"rank_hooks"],
+        bump
+    )]
+    pub rank_hooks: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Signer PDA hooks are called with, required when hooks are registered
+    #[account(
+        seeds = [b"hook_authority"],
+        bump
+    )]
+    pub hook_authority: Option<UncheckedAccount<'info>>,
+
+    /// Guild to count the burn towards, together with `guild_member`
+    #[account(mut)]
+    pub guild: Option<Account<'info, Guild>>,
+
+    #[account(
+        mut,
+        seeds = [b"guild_member", guild_member.guild.as_ref(), user.key().as_ref()],
+        bump = guild_member.bump
+    )]
+    pub guild_member: Option<Account<'info, GuildMember>>,
+
+    /// CHECK: AtomID of `atom_id.referrer`, required when it is set; loaded
+    /// with `load_atom_id`
+    #[account(mut)]
+    pub referrer_atom_id: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Credential the previous schema was registered under, required
+    /// while the config has a previous schema
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub previous_sas_credential: Option<AccountInfo<'info>>,
+
+    /// CHECK: Caller's attestation under the previous schema, closed if it
+    /// still exists; verified against its PDA
+    #[account(mut)]
+    pub previous_sas_attestation: Option<UncheckedAccount<'info>>,
+
+    /// Tier for the new rank; its badge URI replaces the badge's when the
+    /// rank changes. Required then if the AtomID has a badge and the tier
+    /// exists
+    #[account(
+        seeds = [b"rank_tier", rank_tier.rank.to_le_bytes().as_ref()],
+        bump = rank_tier.bump
+    )]
+    pub rank_tier: Option<Account<'info, RankTier>>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAttestation<'info> {
+    #[account(
+        seeds = [b"atomid", atom_id.owner.as_ref()],
+        bump = atom_id.bump,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Attestation under the previous schema, verified against its PDA
+    #[account(mut)]
+    pub old_sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: Attestation under the current schema, verified against its PDA
+    #[account(mut)]
+    pub new_sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: Credential the previous schema was registered under
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub previous_sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS schema account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_schema: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS event authority PDA
+    #[account(
+        seeds = [b"__event_authority"],
+        bump,
+        seeds::program = SAS_PROGRAM_ID
+    )]
+    pub sas_event_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct IssueLinkedAttestation<'info> {
+    #[account(
+        mut,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = atom_id.owner == user.key() @ ErrorCode::Unauthorized,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + LinkedAttestation::INIT_SPACE,
+        seeds = [b"linked_attestation", recipient.key().as_ref()],
+        bump
+    )]
+    pub linked_attestation: Account<'info, LinkedAttestation>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Wallet the attestation is issued to. It signs so nobody can take
+    /// over the attestation address a wallet would use for its own AtomID
+    pub recipient: Signer<'info>,
+
+    /// CHECK: Recipient's own AtomID PDA, must not exist
+    #[account(
+        seeds = [b"atomid", recipient.key().as_ref()],
+        bump
+    )]
+    pub recipient_atom_id: UncheckedAccount<'info>,
+
+    /// CHECK: SAS attestation PDA for the recipient - created by SAS program via CPI
+    #[account(mut)]
+    pub sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS schema account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_schema: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshLinkedAttestation<'info> {
+    #[account(
+        seeds = [b"atomid", linked_attestation.owner.as_ref()],
+        bump = atom_id.bump,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        mut,
+        seeds = [b"linked_attestation", linked_attestation.recipient.as_ref()],
+        bump = linked_attestation.bump
+    )]
+    pub linked_attestation: Account<'info, LinkedAttestation>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Current linked attestation, verified against its PDA
+    #[account(mut)]
+    pub old_sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: Reissued linked attestation, verified against its PDA
+    #[account(mut)]
+    pub new_sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: Credential the current linked attestation was issued under
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub linked_sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS schema account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_schema: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS event authority PDA
+    #[account(
+        seeds = [b"__event_authority"],
+        bump,
+        seeds::program = SAS_PROGRAM_ID
+    )]
+    pub sas_event_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeLinkedAttestation<'info> {
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"linked_attestation", linked_attestation.recipient.as_ref()],
+        bump = linked_attestation.bump,
+        constraint = linked_attestation.owner == user.key()
+            || linked_attestation.recipient == user.key() @ ErrorCode::Unauthorized
+    )]
+    pub linked_attestation: Account<'info, LinkedAttestation>,
+
+    /// CHECK: Identity owner, who paid for and gets back the rent
+    #[account(mut, address = linked_attestation.owner @ ErrorCode::Unauthorized)]
+    pub owner: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid", linked_attestation.owner.as_ref()],
+        bump = atom_id.bump,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: Linked attestation, verified against its PDA
+    #[account(mut)]
+    pub sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: Credential the linked attestation was issued under
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS event authority PDA
+    #[account(
+        seeds = [b"__event_authority"],
+        bump,
+        seeds::program = SAS_PROGRAM_ID
+    )]
+    pub sas_event_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreatePartnerSchema<'info> {
+    #[account(
+        init,
+        payer = sas_manager,
+        space = 8 + PartnerSchema::INIT_SPACE,
+        seeds = [b"partner_schema", sas_schema.key().as_ref()],
+        bump
+    )]
+    pub partner_schema: Account<'info, PartnerSchema>,
+
+    /// CHECK: Key the schema is reserved for
+    pub issuer: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.sas_manager == sas_manager.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub sas_manager: Signer<'info>,
+
+    /// CHECK: PDA used to sign SAS operations
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: UncheckedAccount<'info>,
+
+    /// CHECK: AtomID credential from config
+    #[account(
+        owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram,
+        address = atom_config.sas_credential @ ErrorCode::InvalidSasCredential
+    )]
+    pub sas_credential: UncheckedAccount<'info>,
+
+    /// CHECK: Created by SAS program
+    #[account(mut)]
+    pub sas_schema: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: SAS program for CPI, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterPartnerIssuer<'info> {
+    #[account(
+        init,
+        payer = sas_manager,
+        space = 8 + PartnerIssuer::INIT_SPACE,
+        seeds = [b"partner_issuer", issuer.key().as_ref()],
+        bump
+    )]
+    pub partner_issuer: Account<'info, PartnerIssuer>,
+
+    /// CHECK: Key the partner will sign issuance with
+    pub issuer: UncheckedAccount<'info>,
+
+    /// CHECK: Partner's SAS schema, checked to be under our credential
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_schema: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"partner_schema", sas_schema.key().as_ref()],
+        bump = partner_schema.bump
+    )]
+    pub partner_schema: Account<'info, PartnerSchema>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.sas_manager == sas_manager.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub sas_manager: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemovePartnerIssuer<'info> {
+    #[account(
+        mut,
+        close = sas_manager,
+        seeds = [b"partner_issuer", partner_issuer.authority.as_ref()],
+        bump = partner_issuer.bump
+    )]
+    pub partner_issuer: Account<'info, PartnerIssuer>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.sas_manager == sas_manager.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub sas_manager: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct IssuePartnerAttestation<'info> {
+    #[account(
+        mut,
+        seeds = [b"partner_issuer", authority.key().as_ref()],
+        bump = partner_issuer.bump
+    )]
+    pub partner_issuer: Account<'info, PartnerIssuer>,
+
+    /// Subject's AtomID; the attestation is keyed by its owner
+    #[account(
+        seeds = [b"atomid", atom_id.owner.as_ref()],
+        bump = atom_id.bump,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    /// Registered issuer key, pays for the attestation
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: SAS attestation PDA - created by SAS program via CPI
+    #[account(mut)]
+    pub sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: Partner's SAS schema
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_schema: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokePartnerAttestation<'info> {
+    #[account(
+        seeds = [b"partner_issuer", authority.key().as_ref()],
+        bump = partner_issuer.bump
+    )]
+    pub partner_issuer: Account<'info, PartnerIssuer>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: Partner attestation, verified against its PDA
+    #[account(mut)]
+    pub sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS event authority PDA
+    #[account(
+        seeds = [b"__event_authority"],
+        bump,
+        seeds::program = SAS_PROGRAM_ID
+    )]
+    pub sas_event_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LinkWallet<'info> {
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = atom_id.owner == user.key() @ ErrorCode::Unauthorized,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + WalletLink::INIT_SPACE,
+        seeds = [b"wallet_link", wallet.key().as_ref()],
+        bump
+    )]
+    pub wallet_link: Account<'info, WalletLink>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub wallet: Signer<'info>,
+
+    /// CHECK: Linked wallet's own AtomID PDA, must not exist
+    #[account(
+        seeds = [b"atomid", wallet.key().as_ref()],
+        bump
+    )]
+    pub wallet_atom_id: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnlinkWallet<'info> {
+    #[account(
+        mut,
+        seeds = [b"atomid", wallet_link.owner.as_ref()],
+        bump = atom_id.bump,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"wallet_link", wallet_link.wallet.as_ref()],
+        bump = wallet_link.bump,
+        has_one = owner
+    )]
+    pub wallet_link: Account<'info, WalletLink>,
+
+    /// CHECK: Identity owner, receives the link's rent
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+
+    /// Owner or the linked wallet
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMetadata<'info> {
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = atom_id.owner == user.key() @ ErrorCode::Unauthorized,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Endorse<'info> {
+    #[account(
+        init,
+        payer = endorser,
+        space = 8 + Endorsement::INIT_SPACE,
+        seeds = [b"endorsement", endorser.key().as_ref(), endorsee_atom_id.owner.as_ref()],
+        bump
+    )]
+    pub endorsement: Account<'info, Endorsement>,
+
+    #[account(
+        seeds = [b"atomid", endorser.key().as_ref()],
+        bump = endorser_atom_id.bump,
+        constraint = endorser_atom_id.owner == endorser.key() @ ErrorCode::Unauthorized,
+        constraint = has_current_layout(&endorser_atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub endorser_atom_id: Account<'info, AtomId>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid", endorsee_atom_id.owner.as_ref()],
+        bump = endorsee_atom_id.bump,
+        constraint = has_current_layout(&endorsee_atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub endorsee_atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub endorser: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeEndorsement<'info> {
+    #[account(
+        mut,
+        close = endorser,
+        seeds = [b"endorsement", endorser.key().as_ref(), endorsement.endorsee.as_ref()],
+        bump = endorsement.bump,
+        constraint = endorsement.endorser == endorser.key() @ ErrorCode::Unauthorized
+    )]
+    pub endorsement: Account<'info, Endorsement>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid", endorsement.endorsee.as_ref()],
+        bump = endorsee_atom_id.bump,
+        constraint = has_current_layout(&endorsee_atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub endorsee_atom_id: Account<'info, AtomId>,
+
+    #[account(mut)]
+    pub endorser: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdminUpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    /// Key holding the roles the change needs
+    pub authority: Signer<'info>,
+
+    pub targets: ConfigChangeTargets<'info>,
+}
+
+/// New accounts referenced by a `ConfigChange`, passed so they can be
+/// validated. Omit the ones the change doesn't touch.
+#[derive(Accounts)]
+pub struct ConfigChangeTargets<'info> {
+    pub burn_mint: Option<Account<'info, Mint>>,
+
+    /// CHECK: Parsed as a SAS credential in `ConfigChange::validate`
+    pub sas_credential: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Parsed as a SAS schema in `ConfigChange::validate`
+    pub sas_schema: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct SetRole<'info> {
+    #[account(
+        mut,
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.admin == admin.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(
+        mut,
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.pauser == pauser.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    pub pauser: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct QueueConfigChange<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PendingConfigChange::INIT_SPACE,
+        seeds = [b"config_change", authority.key().as_ref()],
+        bump
+    )]
+    pub pending_config_change: Account<'info, PendingConfigChange>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    /// Key holding the roles the change needs
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub targets: ConfigChangeTargets<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteConfigChange<'info> {
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [b"config_change", proposer.key().as_ref()],
+        bump = pending_config_change.bump,
+        has_one = proposer
+    )]
+    pub pending_config_change: Account<'info, PendingConfigChange>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    /// CHECK: Receives the rent of the executed change
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+
+    pub targets: ConfigChangeTargets<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelConfigChange<'info> {
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [b"config_change", proposer.key().as_ref()],
+        bump = pending_config_change.bump,
+        has_one = proposer
+    )]
+    pub pending_config_change: Account<'info, PendingConfigChange>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    /// Admin, or a key holding the roles the change needs
+    pub authority: Signer<'info>,
+
+    /// CHECK: Receives the rent of the cancelled change
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+/// Fields up to `bump` are the layout first deployed to mainnet; everything
+/// after it was appended later. Old accounts are extended by
+/// `migrate_atomid`, or on the fly by `create_atomid` and `upgrade_atomid`.
+#[account]
+#[derive(InitSpace)]
+pub struct AtomId {
+    pub owner: Pubkey,
+    pub total_burned: u64,
+    pub rank: u8,
+    #[max_len(200)]
+    pub metadata: String,
+    pub created_at_slot: u64,
+    pub updated_at_slot: u64,
+    pub bump: u8,
+    /// See `ATOM_ID_LAYOUT_VERSION`
+    pub layout_version: u8,
+    /// Number of burns (create + upgrades) that built this identity
+    pub burn_count: u64,
+    /// Wallets linked to this identity through `WalletLink` accounts
+    pub linked_wallet_count: u8,
+    /// Whether a soulbound badge mint exists for this identity
+    pub has_badge: bool,
+    /// Claimed `Handle`, empty if none
+    #[max_len(MAX_HANDLE_LEN)]
+    pub handle: String,
+    /// Sum of the weights of live endorsements received
+    pub endorsement_score: u64,
+    pub endorsement_count: u32,
+    /// `hash(prev || event)` over every `ReputationEvent`, see `reputation.rs`
+    pub reputation_hash: [u8; 32],
+    pub status: IdentityStatus,
+    pub kind: IdentityKind,
+    /// Identity that referred this one at creation
+    pub referrer: Option<Pubkey>,
+    /// Identities created with this one as referrer
+    pub referral_count: u32,
+    /// Everything those identities have burned since
+    pub referred_burn_volume: u64,
+    /// Live `LinkedAttestation`s issued from this identity
+    pub linked_attestation_count: u8,
+}
+
+/// Organizations are identities owned by another program's PDA (a DAO
+/// treasury, a multisig vault) rather than a keypair.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub enum IdentityKind {
+    #[default]
+    Individual,
+    Organization,
+}
+
+/// Reverse lookup from a secondary wallet to the identity it belongs to.
+#[account]
+#[derive(InitSpace)]
+pub struct WalletLink {
+    pub wallet: Pubkey,
+    pub owner: Pubkey,
+    pub atom_id: Pubkey,
+    pub linked_at_slot: u64,
+    pub bump: u8,
+}
+
+/// Extra attestation of `owner`'s identity issued with `recipient` as nonce.
+/// One per recipient, since the attestation address is keyed by it.
+#[account]
+#[derive(InitSpace)]
+pub struct LinkedAttestation {
+    pub owner: Pubkey,
+    pub recipient: Pubkey,
+    /// Credential and schema the live attestation was issued under
+    pub sas_credential: Pubkey,
+    pub sas_schema: Pubkey,
+    pub created_at_slot: u64,
+    pub bump: u8,
+}
+
+/// Fields up to `bump` are the layout first deployed to mainnet; everything
+/// after it was appended later, so `migrate_config` can extend old accounts
+/// in place.
+#[account]
+#[derive(InitSpace)]
+pub struct AtomConfig {
+    pub admin: Pubkey,
+    pub min_create_burn: u64,
+    #[max_len(MAX_RANK_TIERS)]
+    pub rank_thresholds: Vec<u64>,
+    pub burn_mint: Pubkey,
+    pub sas_credential: Pubkey,
+    pub sas_schema: Pubkey,
+    pub sas_authority: Pubkey,
+    pub bump: u8,
+    /// See `CONFIG_LAYOUT_VERSION`
+    pub layout_version: u8,
+    pub rank_curve: RankCurve,
+    /// Payload version the current `sas_schema` was registered with
+    pub sas_schema_version: u8,
+    /// Schema (and its credential) in use before the last schema change,
+    /// kept so attestations issued under it can be migrated
+    pub previous_sas_credential: Pubkey,
+    pub previous_sas_schema: Pubkey,
+    pub previous_sas_schema_version: u8,
+    /// Seconds a queued config change must wait before it can be executed.
+    pub config_timelock: i64,
+    pub config_manager: Pubkey,
+    pub pauser: Pubkey,
+    pub sas_manager: Pubkey,
+    pub reward_funder: Pubkey,
+    pub moderator: Pubkey,
+    /// Rank thresholds for organization identities, applied as thresholds
+    /// whatever `rank_curve` is; empty means they rank like individuals
+    #[max_len(MAX_RANK_TIERS)]
+    pub organization_rank_thresholds: Vec<u64>,
+    /// Rank thresholds applied to a guild's pooled burns; empty means guilds
+    /// rank like individuals
+    #[max_len(MAX_RANK_TIERS)]
+    pub guild_rank_thresholds: Vec<u64>,
+    /// Handles of at most this many characters need `short_handle_min_rank`;
+    /// 0 disables the restriction
+    pub short_handle_len: u8,
+    pub short_handle_min_rank: u8,
+    /// Share of a referred identity's rewards, in basis points, that goes to
+    /// its referrer. Set by the reward funder.
+    pub referral_reward_bps: u16,
+    pub paused: bool,
+    /// Bit `r` is set while a `RankTier` exists for rank `r`
+    pub rank_tier_mask: u64,
+    /// Hooks in the `rank_hooks` registry; rank changes need the registry
+    /// while this is nonzero
+    pub rank_hook_count: u8,
+    /// Schema guild attestations are issued under, set once with
+    /// `initialize_guild_sas_schema`
+    pub guild_sas_schema: Pubkey,
+}
+
+/// Keys that can be delegated away from `admin` with `set_role`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    ConfigManager,
+    Pauser,
+    SasManager,
+    RewardFunder,
+    Moderator,
+}
+
+/// Config fields that can be changed after `initialize`. `None` leaves the
+/// field untouched.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct ConfigChange {
+    pub min_create_burn: Option<u64>,
+    #[max_len(MAX_RANK_TIERS)]
+    pub rank_thresholds: Option<Vec<u64>>,
+    pub rank_curve: Option<RankCurve>,
+    pub config_timelock: Option<i64>,
+    pub burn_mint: Option<Pubkey>,
+    pub sas_credential: Option<Pubkey>,
+    pub sas_schema: Option<Pubkey>,
+    pub sas_authority: Option<Pubkey>,
+    /// Required together with `sas_schema`
+    pub sas_schema_version: Option<u8>,
+    pub short_handle_len: Option<u8>,
+    pub short_handle_min_rank: Option<u8>,
+    #[max_len(MAX_RANK_TIERS)]
+    pub organization_rank_thresholds: Option<Vec<u64>>,
+    #[max_len(MAX_RANK_TIERS)]
+    pub guild_rank_thresholds: Option<Vec<u64>>,
+    pub referral_reward_bps: Option<u16>,
+}
+
+impl ConfigChange {
+    fn touches_sas(&self) -> bool {
+        self.sas_credential.is_some()
+            || self.sas_schema.is_some()
+            || self.sas_authority.is_some()
+            || self.sas_schema_version.is_some()
+    }
+
+    fn touches_core(&self) -> bool {
+        self.min_create_burn.is_some()
+            || self.rank_thresholds.is_some()
+            || self.rank_curve.is_some()
+            || self.config_timelock.is_some()
+            || self.burn_mint.is_some()
+            || self.short_handle_len.is_some()
+            || self.short_handle_min_rank.is_some()
+            || self.organization_rank_thresholds.is_some()
+            || self.guild_rank_thresholds.is_some()
+    }
+
+    fn touches_rewards(&self) -> bool {
+        self.referral_reward_bps.is_some()
+    }
+
+    /// SAS fields belong to the SAS manager, reward settings to the reward
+    /// funder, everything else to the config manager. A change touching
+    /// several must be signed by a key holding all of those roles.
+    pub fn authorize(&self, config: &AtomConfig, authority: &Pubkey) -> Result<()> {
+        if self.touches_core() {
+            require_keys_eq!(config.config_manager, *authority, ErrorCode::Unauthorized);
+        }
+
+        if self.touches_sas() {
+            require_keys_eq!(config.sas_manager, *authority, ErrorCode::Unauthorized);
+        }
+
+        if self.touches_rewards() {
+            require_keys_eq!(config.reward_funder, *authority, ErrorCode::Unauthorized);
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self, config: &AtomConfig, targets: &ConfigChangeTargets) -> Result<()> {
+        if let Some(ref thresholds) = self.rank_thresholds {
+            validate_rank_thresholds(thresholds)?;
+        }
+
+        if let Some(ref curve) = self.rank_curve {
+            curve.validate()?;
+        }
+
+        if self.rank_thresholds.is_some() || self.rank_curve.is_some() {
+            let curve = self.rank_curve.unwrap_or(config.rank_curve);
+            let thresholds = self.rank_thresholds.as_ref().unwrap_or(&config.rank_thresholds);
+            validate_tier_coverage(config.rank_tier_mask, curve.tier_count(thresholds))?;
+        }
+
+        if let Some(ref thresholds) = self.organization_rank_thresholds {
+            validate_rank_thresholds(thresholds)?;
+        }
+
+        if let Some(ref thresholds) = self.guild_rank_thresholds {
+            validate_rank_thresholds(thresholds)?;
+        }
+
+        if let Some(timelock) = self.config_timelock {
+            require!(
+                (0..=MAX_CONFIG_TIMELOCK).contains(&timelock),
+                ErrorCode::InvalidTimelock
+            );
+        }
+
+        if let Some(len) = self.short_handle_len {
+            require!(len as usize <= MAX_HANDLE_LEN, ErrorCode::InvalidHandle);
+        }
+
+        if let Some(bps) = self.referral_reward_bps {
+            require!(bps <= MAX_BPS, ErrorCode::InvalidReferralShare);
+        }
+
+        if let Some(burn_mint) = self.burn_mint {
+            let mint = targets
+                .burn_mint
+                .as_ref()
+                .ok_or(ErrorCode::MissingConfigTarget)?;
+            require_keys_eq!(mint.key(), burn_mint, ErrorCode::InvalidBurnMint);
+        }
+
+        let sas_authority = self.sas_authority.unwrap_or(config.sas_authority);
+        if self.sas_authority.is_some() {
+            let (expected, _) = Pubkey::find_program_address(&[b"sas_authority"], &crate::ID);
+            require_keys_eq!(sas_authority, expected, ErrorCode::InvalidSasAuthority);
+        }
+
+        let sas_credential = self.sas_credential.unwrap_or(config.sas_credential);
+        if let Some(credential) = self.sas_credential {
+            let account = targets
+                .sas_credential
+                .as_ref()
+                .ok_or(ErrorCode::MissingConfigTarget)?;
+            require_keys_eq!(account.key(), credential, ErrorCode::InvalidSasCredential);
+            require!(
+                sas_credential_authority(account)? == sas_authority,
+                ErrorCode::InvalidSasCredential
+            );
+        }
+
+        if let Some(schema) = self.sas_schema {
+            let account = targets
+                .sas_schema
+                .as_ref()
+                .ok_or(ErrorCode::MissingConfigTarget)?;
+            require_keys_eq!(account.key(), schema, ErrorCode::InvalidSasSchema);
+            require!(
+                sas_schema_credential(account)? == sas_credential,
+                ErrorCode::InvalidSasSchema
+            );
+
+            // Schemas can be created outside `initialize_sas_schema`, so make
+            // sure the layout is the one this program serializes
+            let version = self
+                .sas_schema_version
+                .ok_or(ErrorCode::UnsupportedSchemaVersion)?;
+            let (layout, _) = atomid_schema_layout(version)?;
+            require!(
+                sas_schema_layout(account)? == layout,
+                ErrorCode::SchemaLayoutMismatch
+            );
+        } else if self.sas_credential.is_some() {
+            // The current schema was registered under the old credential
+            return err!(ErrorCode::InvalidSasSchema);
+        }
+
+        require!(
+            self.sas_schema.is_some() == self.sas_schema_version.is_some(),
+            ErrorCode::UnsupportedSchemaVersion
+        );
+        if let Some(version) = self.sas_schema_version {
+            require!(
+                (1..=ATTESTATION_SCHEMA_VERSION).contains(&version),
+                ErrorCode::UnsupportedSchemaVersion
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// A queued `ConfigChange`. Each proposer has at most one at a time, so one
+/// role holder can't hold up the others.
+#[account]
+#[derive(InitSpace)]
+pub struct PendingConfigChange {
+    pub proposer: Pubkey,
+    pub change: ConfigChange,
+    pub queued_at: i64,
+    pub execute_after: i64,
+    pub bump: u8,
+}
+
+/// Partner schema created under the AtomID credential, reserved for the one
+/// issuer allowed to register with it.
+#[account]
+#[derive(InitSpace)]
+pub struct PartnerSchema {
+    pub schema: Pubkey,
+    pub issuer: Pubkey,
+    pub bump: u8,
+}
+
+/// Partner approved to issue attestations under the AtomID credential, each
+/// bound to its own schema.
+#[account]
+#[derive(InitSpace)]
+pub struct PartnerIssuer {
+    pub authority: Pubkey,
+    pub sas_schema: Pubkey,
+    #[max_len(32)]
+    pub name: String,
+    pub issued_count: u64,
+    pub bump: u8,
+}
+
+/// One AtomID holder vouching for another.
+#[account]
+#[derive(InitSpace)]
+pub struct Endorsement {
+    pub endorser: Pubkey,
+    pub endorsee: Pubkey,
+    /// Endorser's rank when endorsing; removed from the score on revoke
+    pub weight: u64,
+    pub created_at_slot: u64,
+    pub bump: u8,
+}
+
+/// Unique human-readable name for an AtomID, keyed by its normalized form.
+#[account]
+#[derive(InitSpace)]
+pub struct Handle {
+    #[max_len(MAX_HANDLE_LEN)]
+    pub name: String,
+    pub owner: Pubkey,
+    pub atom_id: Pubkey,
+    pub claimed_at_slot: u64,
+    pub bump: u8,
+}
+
+/// Group identity whose members pool burns. Each burn made through
+/// `upgrade_atomid` with the guild attached counts for both the member's
+/// AtomID and the guild.
+#[account]
+#[derive(InitSpace)]
+pub struct Guild {
+    #[max_len(MAX_HANDLE_LEN)]
+    pub name: String,
+    /// Member who manages membership
+    pub admin: Pubkey,
+    pub total_burned: u64,
+    pub burn_count: u64,
+    /// From `guild_rank_thresholds`
+    pub rank: u8,
+    pub member_count: u32,
+    /// Credential and schema the live guild attestation was issued under
+    pub sas_credential: Pubkey,
+    pub sas_schema: Pubkey,
+    pub created_at_slot: u64,
+    pub updated_at_slot: u64,
+    pub bump: u8,
+    /// Paid for the live guild attestation; gets its rent back when it is
+    /// replaced
+    pub attestation_payer: Pubkey,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct GuildMember {
+    pub guild: Pubkey,
+    pub member: Pubkey,
+    /// Burns this member made into the guild
+    pub contributed: u64,
+    pub joined_at_slot: u64,
+    pub bump: u8,
+}
+
+/// Programs notified through `on_rank_changed` when an upgrade changes a rank.
+#[account]
+#[derive(InitSpace)]
+pub struct RankHookRegistry {
+    #[max_len(MAX_RANK_HOOKS)]
+    pub hooks: Vec<RankHook>,
+    pub bump: u8,
+}
+
+/// Per-realm voter weight settings for the governance plugin.
+#[account]
+#[derive(InitSpace)]
+pub struct RealmVoter {
+    pub realm: Pubkey,
+    pub governance_program: Pubkey,
+    pub governing_token_mint: Pubkey,
+    pub formula: VoterWeightFormula,
+    pub max_voter_weight: u64,
+    pub bump: u8,
+}
+
+/// spl-governance addin `VoterWeightRecord`. Anchor's discriminator for this
+/// name is the one the addin interface expects, so field order must match.
+#[account]
+#[derive(InitSpace)]
+pub struct VoterWeightRecord {
+    pub realm: Pubkey,
+    pub governing_token_mint: Pubkey,
+    pub governing_token_owner: Pubkey,
+    pub voter_weight: u64,
+    pub voter_weight_expiry: Option<u64>,
+    pub weight_action: Option<VoterWeightAction>,
+    pub weight_action_target: Option<Pubkey>,
+    pub reserved: [u8; 8],
+}
+
+/// spl-governance addin `MaxVoterWeightRecord`, same layout rules as
+/// `VoterWeightRecord`.
+#[account]
+#[derive(InitSpace)]
+pub struct MaxVoterWeightRecord {
+    pub realm: Pubkey,
+    pub governing_token_mint: Pubkey,
+    pub max_voter_weight: u64,
+    pub max_voter_weight_expiry: Option<u64>,
+    pub reserved: [u8; 8],
+}
+
+/// Optional structured profile for an AtomID, sized to its contents.
+#[account]
+pub struct AtomProfile {
+    pub owner: Pubkey,
+    pub fields: ProfileFields,
+    pub updated_at_slot: u64,
+    pub bump: u8,
+}
+
+/// Concurrent Merkle tree of compressed AtomIDs. Leaves are
+/// `CompressedAtomId::leaf_hash`; the root is the latest changelog entry.
+#[account]
+#[derive(InitSpace)]
+pub struct IdentityTree {
+    pub depth: u8,
+    pub next_index: u32,
+    /// Number of changes applied since creation
+    pub sequence: u64,
+    /// Left-hand nodes the next append hashes against, by height
+    #[max_len(MAX_TREE_DEPTH)]
+    pub filled_subtrees: Vec<[u8; 32]>,
+    #[max_len(CHANGELOG_LEN)]
+    pub changelog: Vec<ChangeLogEntry>,
+    pub active_index: u8,
+    pub bump: u8,
+}
+
+/// Marks that `owner` has a leaf in the identity tree, so a wallet holds at
+/// most one identity, compressed or full. Closed on promotion.
+#[account]
+#[derive(InitSpace)]
+pub struct CompressedAtomIdOwner {
+    pub owner: Pubkey,
+    pub leaf_index: u32,
+    pub bump: u8,
+}
+
+/// Canonical display data for one rank, so clients don't hard-code names.
+#[account]
+#[derive(InitSpace)]
+pub struct RankTier {
+    pub rank: u8,
+    #[max_len(32)]
+    pub name: String,
+    #[max_len(200)]
+    pub badge_uri: String,
+    pub perks: u64,
+    pub bump: u8,
+}
+
+impl AtomConfig {
+    /// Fails while the pauser has user instructions switched off
+    pub fn require_not_paused(&self) -> Result<()> {
+        require!(!self.paused, ErrorCode::ProgramPaused);
+        Ok(())
+    }
+
+    pub fn rank_for(&self, total_burned: u64) -> u8 {
+        self.rank_curve.rank(total_burned, &self.rank_thresholds)
+    }
+
+    /// Organizations use `organization_rank_thresholds` when configured.
+    /// Those are always a plain threshold table: `rank_curve` only shapes
+    /// the individual ranks, and its parameters aren't thresholds that an
+    /// organization table could replace.
+    pub fn rank_for_kind(&self, kind: IdentityKind, total_burned: u64) -> u8 {
+        match kind {
+            IdentityKind::Organization if !self.organization_rank_thresholds.is_empty() => {
+                calculate_rank(total_burned, &self.organization_rank_thresholds)
+            }
+            _ => self.rank_for(total_burned),
+        }
+    }
+
+    /// Guilds use `guild_rank_thresholds` when configured.
+    pub fn guild_rank_for(&self, total_burned: u64) -> u8 {
+        if self.guild_rank_thresholds.is_empty() {
+            self.rank_for(total_burned)
+        } else {
+            calculate_rank(total_burned, &self.guild_rank_thresholds)
+        }
+    }
+
+    pub fn role(&self, role: Role) -> Pubkey {
+        match role {
+            Role::ConfigManager => self.config_manager,
+            Role::Pauser => self.pauser,
+            Role::SasManager => self.sas_manager,
+            Role::RewardFunder => self.reward_funder,
+            Role::Moderator => self.moderator,
+        }
+    }
+
+    fn set_role(&mut self, role: Role, holder: Pubkey) {
+        match role {
+            Role::ConfigManager => self.config_manager = holder,
+            Role::Pauser => self.pauser = holder,
+            Role::SasManager => self.sas_manager = holder,
+            Role::RewardFunder => self.reward_funder = holder,
+            Role::Moderator => self.moderator = holder,
+        }
+    }
+
+    /// Applies `change` and returns the values it replaced, in the same shape.
+    fn apply_change(&mut self, change: &ConfigChange) -> ConfigChange {
+        let mut previous = ConfigChange::default();
+
+        if let Some(min_burn) = change.min_create_burn {
+            previous.min_create_burn = Some(self.min_create_burn);
+            self.min_create_burn = min_burn;
+        }
+
+        if let Some(ref thresholds) = change.rank_thresholds {
+            previous.rank_thresholds =
+                Some(std::mem::replace(&mut self.rank_thresholds, thresholds.clone()));
+        }
+
+        if let Some(curve) = change.rank_curve {
+            previous.rank_curve = Some(self.rank_curve);
+            self.rank_curve = curve;
+        }
+
+        if let Some(ref thresholds) = change.organization_rank_thresholds {
+            previous.organization_rank_thresholds = Some(std::mem::replace(
+                &mut self.organization_rank_thresholds,
+                thresholds.clone(),
+            ));
+        }
+
+        if let Some(ref thresholds) = change.guild_rank_thresholds {
+            previous.guild_rank_thresholds = Some(std::mem::replace(
+                &mut self.guild_rank_thresholds,
+                thresholds.clone(),
+            ));
+        }
+
+        if let Some(timelock) = change.config_timelock {
+            previous.config_timelock = Some(self.config_timelock);
+            self.config_timelock = timelock;
+        }
+
+        if let Some(burn_mint) = change.burn_mint {
+            previous.burn_mint = Some(self.burn_mint);
+            self.burn_mint = burn_mint;
+        }
+
+        if let Some(len) = change.short_handle_len {
+            previous.short_handle_len = Some(self.short_handle_len);
+            self.short_handle_len = len;
+        }
+
+        if let Some(rank) = change.short_handle_min_rank {
+            previous.short_handle_min_rank = Some(self.short_handle_min_rank);
+            self.short_handle_min_rank = rank;
+        }
+
+        if let Some(bps) = change.referral_reward_bps {
+            previous.referral_reward_bps = Some(self.referral_reward_bps);
+            self.referral_reward_bps = bps;
+        }
+
+        if change.sas_schema.is_some() {
+            self.previous_sas_credential = self.sas_credential;
+            self.previous_sas_schema = self.sas_schema;
+            self.previous_sas_schema_version = self.sas_schema_version;
+        }
+
+        if let Some(sas_credential) = change.sas_credential {
+            previous.sas_credential = Some(self.sas_credential);
+            self.sas_credential = sas_credential;
+        }
+
+        if let Some(sas_schema) = change.sas_schema {
+            previous.sas_schema = Some(self.sas_schema);
+            self.sas_schema = sas_schema;
+        }
+
+        if let Some(version) = change.sas_schema_version {
+            previous.sas_schema_version = Some(self.sas_schema_version);
+            self.sas_schema_version = version;
+        }
+
+        if let Some(sas_authority) = change.sas_authority {
+            previous.sas_authority = Some(self.sas_authority);
+            self.sas_authority = sas_authority;
+        }
+
+        previous
+    }
+}
+
+#[event]
+pub struct ConfigInitialized {
+    pub admin: Pubkey,
+    pub min_create_burn: u64,
+    pub burn_mint: Pubkey,
+}
+
+#[event]
+pub struct ConfigMigrated {
+    pub layout_version: u8,
+}
+
+#[event]
+pub struct AtomIdMigrated {
+    pub owner: Pubkey,
+    pub layout_version: u8,
+}
+
+#[event]
+pub struct AtomIdCreated {
+    pub owner: Pubkey,
+    pub total_burned: u64,
+    pub rank: u8,
+}
+
+#[event]
+pub struct AtomIdUpgraded {
+    pub owner: Pubkey,
+    pub total_burned: u64,
+    pub old_rank: u8,
+    pub new_rank: u8,
+}
 
-    /// CHECK: Created by SAS program
-    #[account(mut)]
-    pub sas_credential: UncheckedAccount<'info>,
+#[event]
+pub struct LinkedAttestationIssued {
+    pub owner: Pubkey,
+    pub recipient: Pubkey,
+}
 
-    /// CHECK: SAS program
-    pub sas_program: UncheckedAccount<'info>,
+#[event]
+pub struct LinkedAttestationRevoked {
+    pub owner: Pubkey,
+    pub recipient: Pubkey,
+}
 
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct PartnerSchemaCreated {
+    pub issuer: Pubkey,
+    pub sas_schema: Pubkey,
 }
 
-#[derive(Accounts)]
-pub struct InitializeSasSchema<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
+#[event]
+pub struct PartnerIssuerRegistered {
+    pub authority: Pubkey,
+    pub sas_schema: Pubkey,
+}
 
-    #[account(
-        seeds = [b"sas_authority"],
-        bump
-    )]
-    /// CHECK: PDA used to sign SAS operations
-    pub sas_authority: UncheckedAccount<'info>,
+#[event]
+pub struct PartnerIssuerRemoved {
+    pub authority: Pubkey,
+}
 
-    /// CHECK: Created by SAS program
-    pub sas_credential: UncheckedAccount<'info>,
+#[event]
+pub struct PartnerAttestationIssued {
+    pub issuer: Pubkey,
+    pub subject: Pubkey,
+    pub sas_schema: Pubkey,
+    pub expiry: i64,
+}
 
-    /// CHECK: Created by SAS program
-    #[account(mut)]
-    pub sas_schema: UncheckedAccount<'info>,
+#[event]
+pub struct PartnerAttestationRevoked {
+    pub issuer: Pubkey,
+    pub subject: Pubkey,
+    pub sas_schema: Pubkey,
+}
 
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct WalletLinked {
+    pub owner: Pubkey,
+    pub wallet: Pubkey,
+}
 
-    /// CHECK: SAS program for CPI
-    pub sas_program: UncheckedAccount<'info>,
+#[event]
+pub struct WalletUnlinked {
+    pub owner: Pubkey,
+    pub wallet: Pubkey,
 }
 
-#[derive(Accounts)]
-pub struct CreateAtomId<'info> {
-    #[account(
-        init,
-        payer = user,
-        space = 8 + AtomId::INIT_SPACE,
-        seeds = [b"atomid", user.key().as_ref()],
-        bump
-    )]
-    pub atom_id: Account<'info, AtomId>,
+#[event]
+pub struct BadgeMinted {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+}
 
-    #[account(
-        seeds = [b"atomid_config"],
-        bump = atom_config.bump
-    )]
-    pub atom_config: Account<'info, AtomConfig>,
+#[event]
+pub struct ReputationUpdated {
+    pub owner: Pubkey,
+    pub event: ReputationEvent,
+    pub reputation_hash: [u8; 32],
+}
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+#[event]
+pub struct EndorsementAdded {
+    pub endorser: Pubkey,
+    pub endorsee: Pubkey,
+    pub weight: u64,
+    pub endorsee_score: u64,
+}
 
-    /// CHECK: Validated in instruction handler
-    #[account(mut)]
-    pub user_token_account: AccountInfo<'info>,
+#[event]
+pub struct EndorsementRevoked {
+    pub endorser: Pubkey,
+    pub endorsee: Pubkey,
+    pub weight: u64,
+    pub endorsee_score: u64,
+}
 
-    /// CHECK: Validated in instruction handler
-    #[account(mut)]
-    pub atom_mint: AccountInfo<'info>,
+#[event]
+pub struct HandleClaimed {
+    pub owner: Pubkey,
+    pub name: String,
+}
 
-    /// CHECK: SAS attestation PDA - will be created by SAS program via CPI
-    #[account(mut)]
-    pub sas_attestation: UncheckedAccount<'info>,
+#[event]
+pub struct HandleReleased {
+    pub owner: Pubkey,
+    pub name: String,
+}
 
-    /// CHECK: SAS credential account from config
-    pub sas_credential: AccountInfo<'info>,
+#[event]
+pub struct HandleTransferred {
+    pub name: String,
+    pub from: Pubkey,
+    pub to: Pubkey,
+}
 
-    /// CHECK: SAS schema account from config
-    pub sas_schema: AccountInfo<'info>,
+#[event]
+pub struct GuildCreated {
+    pub guild: Pubkey,
+    pub name: String,
+    pub admin: Pubkey,
+}
 
-    /// CHECK: SAS authority PDA
-    #[account(
-        seeds = [b"sas_authority"],
-        bump
-    )]
-    pub sas_authority: AccountInfo<'info>,
+#[event]
+pub struct GuildMemberAdded {
+    pub guild: Pubkey,
+    pub member: Pubkey,
+}
 
-    /// CHECK: SAS program account
-    pub sas_program: AccountInfo<'info>,
+#[event]
+pub struct GuildMemberRemoved {
+    pub guild: Pubkey,
+    pub member: Pubkey,
+}
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct GuildAdminTransferred {
+    pub guild: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
 }
 
-#[derive(Accounts)]
-pub struct UpgradeAtomId<'info> {
-    #[account(
-        mut,
-        seeds = [b"atomid", user.key().as_ref()],
-        bump = atom_id.bump,
-        constraint = atom_id.owner == user.key() @ ErrorCode::Unauthorized
-    )]
-    pub atom_id: Account<'info, AtomId>,
+#[event]
+pub struct GuildContribution {
+    pub guild: Pubkey,
+    pub member: Pubkey,
+    pub burn_amount: u64,
+    pub total_burned: u64,
+    pub old_rank: u8,
+    pub new_rank: u8,
+}
 
-    #[account(
-        seeds = [b"atomid_config"],
-        bump = atom_config.bump
-    )]
-    pub atom_config: Account<'info, AtomConfig>,
+#[event]
+pub struct GuildAttestationRefreshed {
+    pub guild: Pubkey,
+    pub rank: u8,
+    pub total_burned: u64,
+}
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+#[event]
+pub struct ReferralCredited {
+    pub referrer: Pubkey,
+    pub referred: Pubkey,
+    pub burn_amount: u64,
+    pub referred_burn_volume: u64,
+}
 
-    /// CHECK: Validated in instruction handler
-    #[account(mut)]
-    pub user_token_account: AccountInfo<'info>,
+#[event]
+pub struct ProfileUpdated {
+    pub owner: Pubkey,
+}
 
-    /// CHECK: Validated in instruction handler
-    #[account(mut)]
-    pub atom_mint: AccountInfo<'info>,
+#[event]
+pub struct ProfileClosed {
+    pub owner: Pubkey,
+}
 
-    /// CHECK: Old SAS attestation PDA to be closed - owned by SAS program
-    #[account(mut)]
-    pub old_sas_attestation: UncheckedAccount<'info>,
+#[event]
+pub struct RankHookRegistered {
+    pub program_id: Pubkey,
+    pub compute_units: u32,
+    pub failure_mode: HookFailureMode,
+}
 
-    /// CHECK: New SAS attestation PDA - will be created by SAS program via CPI
-    #[account(mut)]
-    pub new_sas_attestation: UncheckedAccount<'info>,
+#[event]
+pub struct RankHookRemoved {
+    pub program_id: Pubkey,
+}
 
-    /// CHECK: SAS credential account from config
-    pub sas_credential: AccountInfo<'info>,
+#[event]
+pub struct RankHookSkipped {
+    pub program_id: Pubkey,
+    pub owner: Pubkey,
+}
 
-    /// CHECK: SAS schema account from config
-    pub sas_schema: AccountInfo<'info>,
+#[event]
+pub struct RealmVoterConfigured {
+    pub realm: Pubkey,
+    pub governing_token_mint: Pubkey,
+    pub formula: VoterWeightFormula,
+    pub max_voter_weight: u64,
+}
 
-    /// CHECK: SAS authority PDA
-    #[account(
-        seeds = [b"sas_authority"],
-        bump
-    )]
-    pub sas_authority: AccountInfo<'info>,
+#[event]
+pub struct IdentityTreeInitialized {
+    pub depth: u8,
+    pub root: [u8; 32],
+}
 
-    /// CHECK: SAS event authority PDA
-    pub sas_event_authority: AccountInfo<'info>,
+#[event]
+pub struct CompressedAtomIdUpdated {
+    pub leaf_index: u32,
+    pub atom_id: CompressedAtomId,
+    pub root: [u8; 32],
+    pub sequence: u64,
+}
 
-    /// CHECK: SAS program account
-    pub sas_program: AccountInfo<'info>,
+#[event]
+pub struct CompressedAtomIdPromoted {
+    pub owner: Pubkey,
+    pub leaf_index: u32,
+    pub root: [u8; 32],
+    pub sequence: u64,
+}
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct AttestationMigrated {
+    pub owner: Pubkey,
+    pub from_schema: Pubkey,
+    pub to_schema: Pubkey,
+    pub version: u8,
 }
 
-#[derive(Accounts)]
-pub struct UpdateMetadata<'info> {
-    #[account(
-        mut,
-        seeds = [b"atomid", user.key().as_ref()],
-        bump = atom_id.bump,
-        constraint = atom_id.owner == user.key() @ ErrorCode::Unauthorized
-    )]
-    pub atom_id: Account<'info, AtomId>,
+#[event]
+pub struct AtomIdSuspended {
+    pub owner: Pubkey,
+    pub reason: SuspensionReason,
+    pub suspended_at: i64,
+    pub moderator: Pubkey,
+}
 
-    pub user: Signer<'info>,
+#[event]
+pub struct SuspensionAppealed {
+    pub owner: Pubkey,
+    pub appealed_at: i64,
 }
 
-#[derive(Accounts)]
-pub struct AdminUpdateConfig<'info> {
-    #[account(
-        mut,
-        seeds = [b"atomid_config"],
-        bump = atom_config.bump,
-        constraint = atom_config.admin == admin.key() @ ErrorCode::Unauthorized
-    )]
-    pub atom_config: Account<'info, AtomConfig>,
+#[event]
+pub struct AtomIdUnsuspended {
+    pub owner: Pubkey,
+    pub moderator: Pubkey,
+}
 
-    pub admin: Signer<'info>,
+#[event]
+pub struct RoleUpdated {
+    pub role: Role,
+    pub previous: Pubkey,
+    pub holder: Pubkey,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct AtomId {
-    pub owner: Pubkey,
-    pub total_burned: u64,
-    pub rank: u8,
-    #[max_len(200)]
-    pub metadata: String,
-    pub created_at_slot: u64,
-    pub updated_at_slot: u64,
-    pub bump: u8,
+#[event]
+pub struct PausedUpdated {
+    pub paused: bool,
+    pub pauser: Pubkey,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct AtomConfig {
-    pub admin: Pubkey,
-    pub min_create_burn: u64,
-    #[max_len(10)]
-    pub rank_thresholds: Vec<u64>,
-    pub burn_mint: Pubkey,
-    pub sas_credential: Pubkey,
-    pub sas_schema: Pubkey,
-    pub sas_authority: Pubkey,
-    pub bump: u8,
+#[event]
+pub struct ConfigChangeQueued {
+    pub proposer: Pubkey,
+    pub change: ConfigChange,
+    pub execute_after: i64,
 }
 
-fn calculate_rank(total_burned: u64, thresholds: &[u64]) -> u8 {
-    for (i, &threshold) in thresholds.iter().enumerate().rev() {
-        if total_burned >= threshold {
-            return (i + 1) as u8;
-        }
-    }
-    0
+#[event]
+pub struct ConfigChangeExecuted {
+    pub proposer: Pubkey,
+    /// Values replaced by `change`, for the fields it touched
+    pub previous: ConfigChange,
+    pub change: ConfigChange,
 }
 
 #[event]
-pub struct ConfigInitialized {
-    pub admin: Pubkey,
-    pub min_create_burn: u64,
-    pub burn_mint: Pubkey,
+pub struct ConfigChangeCancelled {
+    pub proposer: Pubkey,
+    pub change: ConfigChange,
 }
 
 #[event]
-pub struct AtomIdCreated {
-    pub owner: Pubkey,
-    pub total_burned: u64,
+pub struct RankTierUpdated {
     pub rank: u8,
+    pub name: String,
+    pub badge_uri: String,
+    pub perks: u64,
 }
 
 #[event]
-pub struct AtomIdUpgraded {
-    pub owner: Pubkey,
-    pub total_burned: u64,
-    pub old_rank: u8,
-    pub new_rank: u8,
+pub struct RankTierClosed {
+    pub rank: u8,
 }
 
 #[error_code]
@@ -711,7 +4996,7 @@ pub enum ErrorCode {
     Unauthorized,
     #[msg("Arithmetic overflow")]
     Overflow,
-    #[msg("Too many rank thresholds (max 10)")]
+    #[msg("Too many rank thresholds (max 32)")]
     TooManyRankThresholds,
     #[msg("Rank thresholds must be sorted in ascending order")]
     RankThresholdsNotSorted,
@@ -721,4 +5006,120 @@ pub enum ErrorCode {
     InvalidSasSchema,
     #[msg("Invalid SAS authority")]
     InvalidSasAuthority,
+    #[msg("Invalid rank curve parameters")]
+    InvalidRankCurve,
+    #[msg("Rank is beyond the configured number of tiers")]
+    RankOutOfRange,
+    #[msg("Rank tier name must be 1-32 characters")]
+    InvalidRankTierName,
+    #[msg("Badge URI exceeds maximum length of 200 characters")]
+    BadgeUriTooLong,
+    #[msg("Config changes are timelocked; use queue_config_change")]
+    TimelockActive,
+    #[msg("Config change timelock has not elapsed")]
+    TimelockNotElapsed,
+    #[msg("Config timelock out of range")]
+    InvalidTimelock,
+    #[msg("Program is paused")]
+    ProgramPaused,
+    #[msg("Account for a changed config field was not provided")]
+    MissingConfigTarget,
+    #[msg("Account is not the SAS program or not owned by it")]
+    InvalidSasProgram,
+    #[msg("Account data is not the expected SAS account type")]
+    InvalidSasAccountData,
+    #[msg("Unsupported attestation schema version")]
+    UnsupportedSchemaVersion,
+    #[msg("No previous SAS schema to migrate from")]
+    NoPreviousSchema,
+    #[msg("Attestation address does not match the expected PDA")]
+    InvalidAttestationAddress,
+    #[msg("SAS schema layout does not match the program's attestation payload")]
+    SchemaLayoutMismatch,
+    #[msg("Linked attestation recipient cannot be the owner")]
+    InvalidRecipient,
+    #[msg("Wallet already has its own AtomID")]
+    WalletHasAtomId,
+    #[msg("Too many wallets linked to this AtomID")]
+    TooManyLinkedWallets,
+    #[msg("Wallet is already linked to an AtomID")]
+    WalletAlreadyLinked,
+    #[msg("Badge already minted for this AtomID")]
+    BadgeAlreadyMinted,
+    #[msg("Badge accounts are required to upgrade an AtomID with a badge")]
+    MissingBadgeAccounts,
+    #[msg("Identity tree depth must be between 1 and 20")]
+    InvalidTreeDepth,
+    #[msg("Identity tree is full")]
+    IdentityTreeFull,
+    #[msg("Merkle proof does not match the identity tree")]
+    InvalidMerkleProof,
+    #[msg("Proof root is no longer in the identity tree changelog")]
+    StaleMerkleRoot,
+    #[msg("Profile field too long")]
+    ProfileFieldTooLong,
+    #[msg("URI scheme not allowed for this profile field")]
+    InvalidUriScheme,
+    #[msg("Too many social handles")]
+    TooManySocialHandles,
+    #[msg("Social handle platform and name must be set")]
+    InvalidSocialHandle,
+    #[msg("Profile exceeds the maximum account size")]
+    ProfileTooLarge,
+    #[msg("Handle must be 1-32 characters of a-z, 0-9 or _")]
+    InvalidHandle,
+    #[msg("AtomID already has a handle")]
+    HandleAlreadySet,
+    #[msg("Rank too low for a handle this short")]
+    RankTooLowForHandle,
+    #[msg("Cannot endorse your own AtomID")]
+    InvalidEndorsee,
+    #[msg("AtomID is suspended")]
+    IdentitySuspended,
+    #[msg("AtomID is not suspended")]
+    IdentityNotSuspended,
+    #[msg("Suspension already appealed")]
+    AlreadyAppealed,
+    #[msg("Issuer name must be 1-32 characters")]
+    InvalidIssuerName,
+    #[msg("Attestation expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Account is not an spl-governance realm")]
+    InvalidRealm,
+    #[msg("Rank hook already registered, not found, or has an invalid compute budget")]
+    InvalidRankHook,
+    #[msg("Too many rank hooks registered")]
+    TooManyRankHooks,
+    #[msg("Required rank hook could not be called")]
+    RankHookUnavailable,
+    #[msg("Rank hook used more compute than registered")]
+    RankHookComputeExceeded,
+    #[msg("Organization owner must be a program-derived address")]
+    OwnerNotProgramAddress,
+    #[msg("Guild name must be 1-32 characters of a-z, 0-9 or _")]
+    InvalidGuildName,
+    #[msg("Not a member of this guild")]
+    NotGuildMember,
+    #[msg("The guild admin can't leave or be removed; transfer admin first")]
+    GuildAdminCannotLeave,
+    #[msg("Referrer must be another active AtomID, passed with its account")]
+    InvalidReferrer,
+    #[msg("Referral share can't exceed 10000 basis points")]
+    InvalidReferralShare,
+    #[msg("Account already uses the current layout")]
+    AlreadyMigrated,
+    #[msg("AtomID uses an older layout; call migrate_atomid first")]
+    AtomIdNotMigrated,
+    #[msg("Rank tiers must cover every rank the rank curve produces")]
+    RankTiersMismatch,
+    #[msg("Attestation accounts for the previous schema are required")]
+    PreviousAttestationRequired,
+    #[msg("Wallet already has a compressed AtomID; promote it instead")]
+    WalletHasCompressedAtomId,
+    #[msg("Every linked attestation of the identity must be passed, once each")]
+    LinkedAttestationsRequired,
+    #[msg("Schema layout must have 1-16 known SAS types, each with a 1-32 character name")]
+    InvalidSchemaLayout,
+    #[msg("Wallet holds a linked attestation; revoke it before creating an AtomID")]
+    WalletHasLinkedAttestation,
 }
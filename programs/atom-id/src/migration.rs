@@ -0,0 +1,392 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Transfer};
+
+use crate::{
+    AtomConfig, AtomId, AtomIdMigrated, ConfigMigrated, ErrorCode, IdentityKind, IdentityStatus,
+    RankCurve, DEFAULT_CONFIG_TIMELOCK,
+};
+
+/// Layout `AtomConfig` accounts are written with. Version 0 is the original
+/// mainnet layout, which ended at `bump`.
+pub const CONFIG_LAYOUT_VERSION: u8 = 1;
+
+/// Same for `AtomId`.
+pub const ATOM_ID_LAYOUT_VERSION: u8 = 1;
+
+/// `AtomConfig` as first deployed.
+#[derive(AnchorDeserialize)]
+struct LegacyAtomConfig {
+    admin: Pubkey,
+    min_create_burn: u64,
+    rank_thresholds: Vec<u64>,
+    burn_mint: Pubkey,
+    sas_credential: Pubkey,
+    sas_schema: Pubkey,
+    sas_authority: Pubkey,
+    bump: u8,
+}
+
+impl From<LegacyAtomConfig> for AtomConfig {
+    /// Fills the appended fields the way `initialize` would, except for the
+    /// schema version: the original program wrote v1 payloads.
+    fn from(legacy: LegacyAtomConfig) -> Self {
+        AtomConfig {
+            admin: legacy.admin,
+            min_create_burn: legacy.min_create_burn,
+            rank_thresholds: legacy.rank_thresholds,
+            burn_mint: legacy.burn_mint,
+            sas_credential: legacy.sas_credential,
+            sas_schema: legacy.sas_schema,
+            sas_authority: legacy.sas_authority,
+            bump: legacy.bump,
+            layout_version: CONFIG_LAYOUT_VERSION,
+            rank_curve: RankCurve::default(),
+            sas_schema_version: 1,
+            previous_sas_credential: Pubkey::default(),
+            previous_sas_schema: Pubkey::default(),
+            previous_sas_schema_version: 0,
+            config_timelock: DEFAULT_CONFIG_TIMELOCK,
+            config_manager: legacy.admin,
+            pauser: legacy.admin,
+            sas_manager: legacy.admin,
+            reward_funder: legacy.admin,
+            moderator: legacy.admin,
+            organization_rank_thresholds: Vec::new(),
+            guild_rank_thresholds: Vec::new(),
+            short_handle_len: 0,
+            short_handle_min_rank: 0,
+            referral_reward_bps: 0,
+            paused: false,
+            rank_tier_mask: 0,
+            rank_hook_count: 0,
+            guild_sas_schema: Pubkey::default(),
+        }
+    }
+}
+
+/// `AtomId` as first deployed.
+#[derive(AnchorDeserialize)]
+struct LegacyAtomId {
+    owner: Pubkey,
+    total_burned: u64,
+    rank: u8,
+    metadata: String,
+    created_at_slot: u64,
+    updated_at_slot: u64,
+    bump: u8,
+}
+
+impl From<LegacyAtomId> for AtomId {
+    fn from(legacy: LegacyAtomId) -> Self {
+        AtomId {
+            owner: legacy.owner,
+            total_burned: legacy.total_burned,
+            rank: legacy.rank,
+            metadata: legacy.metadata,
+            created_at_slot: legacy.created_at_slot,
+            updated_at_slot: legacy.updated_at_slot,
+            bump: legacy.bump,
+            layout_version: ATOM_ID_LAYOUT_VERSION,
+            // Upgrades weren't counted before; the creation burn at least
+            // happened
+            burn_count: 1,
+            linked_wallet_count: 0,
+            has_badge: false,
+            handle: String::new(),
+            endorsement_score: 0,
+            endorsement_count: 0,
+            reputation_hash: [0; 32],
+            status: IdentityStatus::Active,
+            kind: IdentityKind::Individual,
+            referrer: None,
+            referral_count: 0,
+            referred_burn_volume: 0,
+            linked_attestation_count: 0,
+        }
+    }
+}
+
+/// Grows `account` to `len` bytes, topping its rent up from `payer`.
+pub fn resize_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    len: usize,
+) -> Result<()> {
+    let shortfall = Rent::get()?
+        .minimum_balance(len)
+        .saturating_sub(account.lamports());
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    account.realloc(len, false)?;
+    Ok(())
+}
+
+/// Rewrites a config account from an older layout into the current one.
+/// Only the admin recorded in the old account may do this.
+pub fn migrate_config_account<'info>(
+    config: &AccountInfo<'info>,
+    admin: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    // Accounts are allocated at their full size, so an old layout is
+    // recognised by length; stale bytes past the old `bump` make decoding
+    // with the current layout unreliable
+    let migrated: AtomConfig = {
+        let data = config.try_borrow_data()?;
+        require!(
+            data.len() < 8 + AtomConfig::INIT_SPACE,
+            ErrorCode::AlreadyMigrated
+        );
+        require!(
+            data.starts_with(AtomConfig::DISCRIMINATOR),
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        LegacyAtomConfig::deserialize(&mut &data[8..])?.into()
+    };
+    require_keys_eq!(migrated.admin, admin.key(), ErrorCode::Unauthorized);
+
+    resize_account(config, admin, system_program, 8 + AtomConfig::INIT_SPACE)?;
+    let mut data = config.try_borrow_mut_data()?;
+    migrated.try_serialize(&mut &mut data[..])?;
+
+    emit!(ConfigMigrated {
+        layout_version: CONFIG_LAYOUT_VERSION,
+    });
+
+    Ok(())
+}
+
+/// Reads an AtomID account, first rewriting it into the current layout if it
+/// still uses an older one (recognised by its size), with `payer` covering
+/// the extra rent. Also returns whether it was migrated.
+fn migrate_atom_id_in_place<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<(AtomId, bool)> {
+    require_keys_eq!(
+        *account.owner,
+        crate::ID,
+        anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram
+    );
+
+    let (atom_id, migrated) = {
+        let data = account.try_borrow_data()?;
+        if data.len() < 8 + AtomId::INIT_SPACE {
+            require!(
+                data.starts_with(AtomId::DISCRIMINATOR),
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+            let legacy: AtomId = LegacyAtomId::deserialize(&mut &data[8..])?.into();
+            (legacy, true)
+        } else {
+            (AtomId::try_deserialize(&mut &data[..])?, false)
+        }
+    };
+
+    let address = Pubkey::create_program_address(
+        &[b"atomid", atom_id.owner.as_ref(), &[atom_id.bump]],
+        &crate::ID,
+    )
+    .map_err(|_| anchor_lang::error::ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(
+        address,
+        account.key(),
+        anchor_lang::error::ErrorCode::ConstraintSeeds
+    );
+
+    if migrated {
+        resize_account(account, payer, system_program, 8 + AtomId::INIT_SPACE)?;
+        store_atom_id(account, &atom_id)?;
+        emit!(AtomIdMigrated {
+            owner: atom_id.owner,
+            layout_version: ATOM_ID_LAYOUT_VERSION,
+        });
+    }
+
+    Ok((atom_id, migrated))
+}
+
+/// Loads an AtomID passed as an unchecked account, migrating it first if
+/// needed. Write changes back with `store_atom_id`.
+pub fn load_atom_id<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<AtomId> {
+    Ok(migrate_atom_id_in_place(account, payer, system_program)?.0)
+}
+
+pub fn store_atom_id(account: &AccountInfo, atom_id: &AtomId) -> Result<()> {
+    let mut data = account.try_borrow_mut_data()?;
+    atom_id.try_serialize(&mut &mut data[..])
+}
+
+/// Whether an AtomID account has been grown to the current layout. An older
+/// account still decodes as `AtomId`, the new fields coming out of its
+/// metadata padding, so typed `Account<AtomId>` fields check this before
+/// anything is written to them; migration would drop those writes.
+pub fn has_current_layout(account: &AccountInfo) -> bool {
+    account.data_len() >= 8 + AtomId::INIT_SPACE
+}
+
+/// Migrates one AtomID, erroring if it is already current.
+pub fn migrate_atom_id_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let (_, migrated) = migrate_atom_id_in_place(account, payer, system_program)?;
+    require!(migrated, ErrorCode::AlreadyMigrated);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    /// CHECK: Config in an older layout, decoded in the handler
+    #[account(
+        mut,
+        seeds = [b"atomid_config"],
+        bump,
+        owner = crate::ID
+    )]
+    pub atom_config: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAtomId<'info> {
+    /// CHECK: AtomID in an older layout; its address is checked against the
+    /// decoded owner and bump
+    #[account(mut)]
+    pub atom_id: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sizes the original program allocated, which `migrate_*` relies on to
+    /// recognise old accounts.
+    const LEGACY_ATOM_ID_LEN: usize = 8 + 32 + 8 + 1 + (4 + 200) + 8 + 8 + 1;
+    const LEGACY_CONFIG_LEN: usize = 8 + 32 + 8 + (4 + 10 * 8) + 4 * 32 + 1;
+
+    const _: () = assert!(LEGACY_ATOM_ID_LEN < 8 + AtomId::INIT_SPACE);
+    const _: () = assert!(LEGACY_CONFIG_LEN < 8 + AtomConfig::INIT_SPACE);
+
+    #[test]
+    fn legacy_atom_id_keeps_original_prefix() {
+        let owner = Pubkey::new_unique();
+        let mut legacy = Vec::new();
+        owner.serialize(&mut legacy).unwrap();
+        5_000u64.serialize(&mut legacy).unwrap();
+        2u8.serialize(&mut legacy).unwrap();
+        "hello".to_string().serialize(&mut legacy).unwrap();
+        10u64.serialize(&mut legacy).unwrap();
+        20u64.serialize(&mut legacy).unwrap();
+        254u8.serialize(&mut legacy).unwrap();
+        legacy.resize(LEGACY_ATOM_ID_LEN - 8, 0xaa);
+
+        let atom_id: AtomId = LegacyAtomId::deserialize(&mut &legacy[..]).unwrap().into();
+        assert_eq!(atom_id.owner, owner);
+        assert_eq!(atom_id.total_burned, 5_000);
+        assert_eq!(atom_id.rank, 2);
+        assert_eq!(atom_id.metadata, "hello");
+        assert_eq!(atom_id.bump, 254);
+        assert_eq!(atom_id.layout_version, ATOM_ID_LAYOUT_VERSION);
+
+        // The SDK reads these fields at fixed offsets / after `metadata`
+        let mut current = Vec::new();
+        atom_id.serialize(&mut current).unwrap();
+        let prefix = 32 + 8 + 1 + (4 + 5) + 8 + 8 + 1;
+        assert_eq!(current[..prefix], legacy[..prefix]);
+    }
+
+    fn legacy_account_data(owner: Pubkey, bump: u8) -> Vec<u8> {
+        let mut data = AtomId::DISCRIMINATOR.to_vec();
+        owner.serialize(&mut data).unwrap();
+        5_000u64.serialize(&mut data).unwrap();
+        2u8.serialize(&mut data).unwrap();
+        "hello".to_string().serialize(&mut data).unwrap();
+        10u64.serialize(&mut data).unwrap();
+        20u64.serialize(&mut data).unwrap();
+        bump.serialize(&mut data).unwrap();
+        data.resize(LEGACY_ATOM_ID_LEN, 0);
+        data
+    }
+
+    #[test]
+    fn suspension_survives_migration() {
+        let owner = Pubkey::new_unique();
+        let (address, bump) =
+            Pubkey::find_program_address(&[b"atomid", owner.as_ref()], &crate::ID);
+        let mut data = legacy_account_data(owner, bump);
+        let mut lamports = 0;
+        let account = AccountInfo::new(
+            &address,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &crate::ID,
+            false,
+            0,
+        );
+
+        // A legacy account decodes as the current struct, so typed
+        // instructions like `suspend_atomid` must turn it away
+        let decoded = AtomId::try_deserialize(&mut &account.data.borrow()[..]).unwrap();
+        assert_eq!(decoded.layout_version, 0);
+        assert!(!has_current_layout(&account));
+
+        // Once migrated, a suspension is kept when the account is loaded again
+        let mut atom_id: AtomId =
+            LegacyAtomId::deserialize(&mut &legacy_account_data(owner, bump)[8..])
+                .unwrap()
+                .into();
+        atom_id.status = IdentityStatus::Suspended {
+            reason: crate::SuspensionReason::Fraud,
+            suspended_at: 1,
+            appealed_at: None,
+        };
+        let mut data = vec![0; 8 + AtomId::INIT_SPACE];
+        let mut lamports = 0;
+        let account = AccountInfo::new(
+            &address,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &crate::ID,
+            false,
+            0,
+        );
+        store_atom_id(&account, &atom_id).unwrap();
+        assert!(has_current_layout(&account));
+
+        let (loaded, migrated) = migrate_atom_id_in_place(&account, &account, &account).unwrap();
+        assert!(!migrated);
+        assert!(!loaded.is_active());
+        assert_eq!(loaded.status, atom_id.status);
+    }
+}
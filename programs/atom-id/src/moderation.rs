@@ -0,0 +1,139 @@
+use anchor_lang::prelude::*;
+
+use crate::{has_current_layout, AtomConfig, AtomId, ErrorCode, SAS_PROGRAM_ID};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum SuspensionReason {
+    Fraud,
+    Impersonation,
+    Spam,
+    Sybil,
+    Other,
+}
+
+/// Moderation state of an identity. A suspended identity keeps its data but
+/// loses its attestation and can't renew it until unsuspended.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub enum IdentityStatus {
+    #[default]
+    Active,
+    Suspended {
+        reason: SuspensionReason,
+        suspended_at: i64,
+        /// Set once the owner appeals
+        appealed_at: Option<i64>,
+    },
+}
+
+impl AtomId {
+    pub fn is_active(&self) -> bool {
+        self.status == IdentityStatus::Active
+    }
+
+    /// Guard for anything that renews or relies on the identity's
+    /// attestation. Integrators reading `AtomId` over CPI should do the same.
+    pub fn require_active(&self) -> Result<()> {
+        require!(self.is_active(), ErrorCode::IdentitySuspended);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SuspendAtomId<'info> {
+    #[account(
+        mut,
+        seeds = [b"atomid", atom_id.owner.as_ref()],
+        bump = atom_id.bump,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.moderator == moderator.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub moderator: Signer<'info>,
+
+    /// CHECK: Identity owner, refunded the closed attestations' rent
+    #[account(mut, address = atom_id.owner @ ErrorCode::Unauthorized)]
+    pub owner: UncheckedAccount<'info>,
+
+    /// CHECK: Identity's attestation, verified against its PDA
+    #[account(mut)]
+    pub sas_attestation: UncheckedAccount<'info>,
+
+    /// CHECK: SAS credential account from config
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_credential: AccountInfo<'info>,
+
+    /// CHECK: SAS authority PDA
+    #[account(
+        seeds = [b"sas_authority"],
+        bump
+    )]
+    pub sas_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS event authority PDA
+    #[account(
+        seeds = [b"__event_authority"],
+        bump,
+        seeds::program = SAS_PROGRAM_ID
+    )]
+    pub sas_event_authority: AccountInfo<'info>,
+
+    /// CHECK: SAS program account, checked against SAS_PROGRAM_ID
+    #[account(address = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub sas_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Credential the previous schema was registered under, required
+    /// while the config has a previous schema
+    #[account(owner = SAS_PROGRAM_ID @ ErrorCode::InvalidSasProgram)]
+    pub previous_sas_credential: Option<AccountInfo<'info>>,
+
+    /// CHECK: Identity's attestation under the previous schema; verified
+    /// against its PDA
+    #[account(mut)]
+    pub previous_sas_attestation: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct AppealSuspension<'info> {
+    #[account(
+        mut,
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = atom_id.owner == user.key() @ ErrorCode::Unauthorized,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnsuspendAtomId<'info> {
+    #[account(
+        mut,
+        seeds = [b"atomid", atom_id.owner.as_ref()],
+        bump = atom_id.bump,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.moderator == moderator.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    pub moderator: Signer<'info>,
+}
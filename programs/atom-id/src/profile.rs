@@ -0,0 +1,159 @@
+use anchor_lang::prelude::*;
+
+use crate::{has_current_layout, AtomConfig, AtomId, AtomProfile, ErrorCode};
+
+/// Upper bound on an `AtomProfile` account, discriminator included.
+pub const MAX_PROFILE_SPACE: usize = 1024;
+pub const MAX_SOCIAL_HANDLES: usize = 5;
+
+const MAX_DISPLAY_NAME_LEN: usize = 32;
+const MAX_URI_LEN: usize = 200;
+const MAX_PLATFORM_LEN: usize = 16;
+const MAX_PROFILE_HANDLE_LEN: usize = 32;
+
+const AVATAR_SCHEMES: [&str; 3] = ["https://", "ipfs://", "ar://"];
+const WEBSITE_SCHEMES: [&str; 1] = ["https://"];
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SocialHandle {
+    /// e.g. "x", "github", "telegram"
+    pub platform: String,
+    pub handle: String,
+}
+
+/// Typed profile contents. Empty strings mean the field is unset.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProfileFields {
+    pub display_name: String,
+    pub avatar_uri: String,
+    pub website: String,
+    pub socials: Vec<SocialHandle>,
+    /// Hash of off-chain profile content (bio, banner, ...), if any
+    pub content_hash: Option<[u8; 32]>,
+}
+
+impl ProfileFields {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.display_name.len() <= MAX_DISPLAY_NAME_LEN,
+            ErrorCode::ProfileFieldTooLong
+        );
+        validate_uri(&self.avatar_uri, &AVATAR_SCHEMES)?;
+        validate_uri(&self.website, &WEBSITE_SCHEMES)?;
+
+        require!(
+            self.socials.len() <= MAX_SOCIAL_HANDLES,
+            ErrorCode::TooManySocialHandles
+        );
+        for social in &self.socials {
+            require!(
+                !social.platform.is_empty() && !social.handle.is_empty(),
+                ErrorCode::InvalidSocialHandle
+            );
+            require!(
+                social.platform.len() <= MAX_PLATFORM_LEN && social.handle.len() <= MAX_PROFILE_HANDLE_LEN,
+                ErrorCode::ProfileFieldTooLong
+            );
+        }
+
+        require!(
+            AtomProfile::space_for(self) <= MAX_PROFILE_SPACE,
+            ErrorCode::ProfileTooLarge
+        );
+        Ok(())
+    }
+}
+
+fn validate_uri(uri: &str, schemes: &[&str]) -> Result<()> {
+    if uri.is_empty() {
+        return Ok(());
+    }
+    require!(uri.len() <= MAX_URI_LEN, ErrorCode::ProfileFieldTooLong);
+    require!(
+        schemes
+            .iter()
+            .any(|scheme| uri.len() > scheme.len() && uri.starts_with(scheme)),
+        ErrorCode::InvalidUriScheme
+    );
+    Ok(())
+}
+
+impl AtomProfile {
+    /// Account size needed to hold `fields`, discriminator included.
+    pub fn space_for(fields: &ProfileFields) -> usize {
+        8 + 32 + fields.try_to_vec().unwrap().len() + 8 + 1
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(fields: ProfileFields)]
+pub struct CreateProfile<'info> {
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(
+        init,
+        payer = user,
+        space = AtomProfile::space_for(&fields),
+        seeds = [b"atom_profile", user.key().as_ref()],
+        bump
+    )]
+    pub profile: Account<'info, AtomProfile>,
+
+    #[account(
+        seeds = [b"atomid", user.key().as_ref()],
+        bump = atom_id.bump,
+        constraint = atom_id.owner == user.key() @ ErrorCode::Unauthorized,
+        constraint = has_current_layout(&atom_id.to_account_info()) @ ErrorCode::AtomIdNotMigrated
+    )]
+    pub atom_id: Account<'info, AtomId>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(fields: ProfileFields)]
+pub struct UpdateProfile<'info> {
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(
+        mut,
+        realloc = AtomProfile::space_for(&fields),
+        realloc::payer = user,
+        realloc::zero = false,
+        seeds = [b"atom_profile", user.key().as_ref()],
+        bump = profile.bump,
+        constraint = profile.owner == user.key() @ ErrorCode::Unauthorized
+    )]
+    pub profile: Account<'info, AtomProfile>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseProfile<'info> {
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"atom_profile", user.key().as_ref()],
+        bump = profile.bump,
+        constraint = profile.owner == user.key() @ ErrorCode::Unauthorized
+    )]
+    pub profile: Account<'info, AtomProfile>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
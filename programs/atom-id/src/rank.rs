@@ -0,0 +1,368 @@
+use anchor_lang::prelude::*;
+
+use crate::{AtomConfig, ErrorCode, RankTier};
+
+pub const MAX_RANK_TIERS: usize = 32;
+
+/// How `total_burned` maps to a rank. `Thresholds` reads the sorted
+/// `rank_thresholds` list from config; the other curves are parametric and
+/// ignore it.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub enum RankCurve {
+    #[default]
+    Thresholds,
+    /// Rank 1 at `base_amount`, each further rank needs `growth` times more.
+    Logarithmic {
+        base_amount: u64,
+        growth: u64,
+        max_rank: u8,
+    },
+    /// Rank `r` needs `r * r * unit` burned.
+    SquareRoot { unit: u64, max_rank: u8 },
+}
+
+impl RankCurve {
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            RankCurve::Thresholds => {}
+            RankCurve::Logarithmic {
+                base_amount,
+                growth,
+                max_rank,
+            } => {
+                require!(base_amount > 0, ErrorCode::InvalidRankCurve);
+                require!(growth >= 2, ErrorCode::InvalidRankCurve);
+                require!(
+                    max_rank > 0 && max_rank as usize <= MAX_RANK_TIERS,
+                    ErrorCode::InvalidRankCurve
+                );
+            }
+            RankCurve::SquareRoot { unit, max_rank } => {
+                require!(unit > 0, ErrorCode::InvalidRankCurve);
+                require!(
+                    max_rank > 0 && max_rank as usize <= MAX_RANK_TIERS,
+                    ErrorCode::InvalidRankCurve
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rank(&self, total_burned: u64, thresholds: &[u64]) -> u8 {
+        match *self {
+            RankCurve::Thresholds => calculate_rank(total_burned, thresholds),
+            RankCurve::Logarithmic {
+                base_amount,
+                growth,
+                max_rank,
+            } => {
+                let mut rank = 0u8;
+                let mut next = base_amount;
+                while rank < max_rank && total_burned >= next {
+                    rank += 1;
+                    next = match next.checked_mul(growth) {
+                        Some(n) => n,
+                        None => break,
+                    };
+                }
+                rank
+            }
+            RankCurve::SquareRoot { unit, max_rank } => {
+                let root = isqrt(total_burned / unit);
+                root.min(max_rank as u64) as u8
+            }
+        }
+    }
+
+    /// Number of ranks above 0 this curve can produce.
+    pub fn tier_count(&self, thresholds: &[u64]) -> usize {
+        match *self {
+            RankCurve::Thresholds => thresholds.len(),
+            RankCurve::Logarithmic { max_rank, .. } | RankCurve::SquareRoot { max_rank, .. } => {
+                max_rank as usize
+            }
+        }
+    }
+}
+
+pub fn validate_rank_thresholds(thresholds: &[u64]) -> Result<()> {
+    require!(
+        thresholds.len() <= MAX_RANK_TIERS,
+        ErrorCode::TooManyRankThresholds
+    );
+    require!(
+        thresholds.windows(2).all(|w| w[0] <= w[1]),
+        ErrorCode::RankThresholdsNotSorted
+    );
+    Ok(())
+}
+
+/// Bit of `AtomConfig.rank_tier_mask` that is set while a `RankTier` exists
+/// for `rank`.
+pub fn rank_tier_bit(rank: u8) -> u64 {
+    1u64 << rank
+}
+
+/// Once any tier exists, every rank in `1..=tier_count` must have one. A tier
+/// for rank 0 is optional, and tiers above the top rank may be set up ahead
+/// of a curve change.
+pub fn validate_tier_coverage(rank_tier_mask: u64, tier_count: usize) -> Result<()> {
+    if rank_tier_mask == 0 {
+        return Ok(());
+    }
+    let expected = ((1u64 << (tier_count + 1)) - 1) & !1;
+    require!(
+        rank_tier_mask & expected == expected,
+        ErrorCode::RankTiersMismatch
+    );
+    Ok(())
+}
+
+pub fn validate_rank_tier(name: &str, badge_uri: &str) -> Result<()> {
+    require!(
+        !name.is_empty() && name.len() <= 32,
+        ErrorCode::InvalidRankTierName
+    );
+    require!(badge_uri.len() <= 200, ErrorCode::BadgeUriTooLong);
+    Ok(())
+}
+
+pub fn calculate_rank(total_burned: u64, thresholds: &[u64]) -> u8 {
+    thresholds.partition_point(|&threshold| threshold <= total_burned) as u8
+}
+
+fn isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n / 2 + 1;
+    let mut y = (x + n / x) / 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+#[derive(Accounts)]
+#[instruction(rank: u8)]
+pub struct CreateRankTier<'info> {
+    #[account(
+        init,
+        payer = config_manager,
+        space = 8 + RankTier::INIT_SPACE,
+        seeds = [b"rank_tier", rank.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub rank_tier: Account<'info, RankTier>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.config_manager == config_manager.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub config_manager: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRankTier<'info> {
+    #[account(
+        mut,
+        seeds = [b"rank_tier", rank_tier.rank.to_le_bytes().as_ref()],
+        bump = rank_tier.bump
+    )]
+    pub rank_tier: Account<'info, RankTier>,
+
+    #[account(
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.config_manager == config_manager.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    pub config_manager: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRankTier<'info> {
+    #[account(
+        mut,
+        close = config_manager,
+        seeds = [b"rank_tier", rank_tier.rank.to_le_bytes().as_ref()],
+        bump = rank_tier.bump
+    )]
+    pub rank_tier: Account<'info, RankTier>,
+
+    #[account(
+        mut,
+        seeds = [b"atomid_config"],
+        bump = atom_config.bump,
+        constraint = atom_config.config_manager == config_manager.key() @ ErrorCode::Unauthorized
+    )]
+    pub atom_config: Account<'info, AtomConfig>,
+
+    #[account(mut)]
+    pub config_manager: Signer<'info>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curves() -> Vec<(RankCurve, Vec<u64>)> {
+        vec![
+            (RankCurve::Thresholds, vec![]),
+            (RankCurve::Thresholds, vec![1_000, 5_000, 5_000, 100_000, u64::MAX]),
+            (RankCurve::Thresholds, (1..=MAX_RANK_TIERS as u64).map(|i| i * 1_000).collect()),
+            (
+                RankCurve::Logarithmic {
+                    base_amount: 1_000,
+                    growth: 10,
+                    max_rank: 12,
+                },
+                vec![],
+            ),
+            (
+                RankCurve::Logarithmic {
+                    base_amount: 1,
+                    growth: 2,
+                    max_rank: MAX_RANK_TIERS as u8,
+                },
+                vec![],
+            ),
+            (
+                RankCurve::SquareRoot {
+                    unit: 1,
+                    max_rank: MAX_RANK_TIERS as u8,
+                },
+                vec![],
+            ),
+            (
+                RankCurve::SquareRoot {
+                    unit: 1_000_000,
+                    max_rank: 7,
+                },
+                vec![],
+            ),
+        ]
+    }
+
+    /// Sorted burn totals: edge values, every power of two and its
+    /// neighbours, and xorshift samples.
+    fn samples() -> Vec<u64> {
+        let mut out = vec![0, 1, 2, u64::MAX - 1, u64::MAX];
+        for shift in 0..64 {
+            let p = 1u64 << shift;
+            out.extend([p - 1, p, p.saturating_add(1)]);
+        }
+        let mut x = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..10_000 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            out.push(x >> (x % 64));
+        }
+        out.sort_unstable();
+        out
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        let totals = samples();
+        for (curve, thresholds) in curves() {
+            let ranks: Vec<u8> = totals.iter().map(|&t| curve.rank(t, &thresholds)).collect();
+            assert!(
+                ranks.windows(2).all(|w| w[0] <= w[1]),
+                "{curve:?} is not monotonic"
+            );
+        }
+    }
+
+    #[test]
+    fn ranks_are_clamped_to_tier_count() {
+        for (curve, thresholds) in curves() {
+            let tiers = curve.tier_count(&thresholds);
+            assert!(tiers <= MAX_RANK_TIERS);
+            for total in samples() {
+                assert!(curve.rank(total, &thresholds) as usize <= tiers, "{curve:?} at {total}");
+            }
+        }
+    }
+
+    #[test]
+    fn edge_totals() {
+        for (curve, thresholds) in curves() {
+            let tiers = curve.tier_count(&thresholds);
+            if thresholds.first() != Some(&0) {
+                assert_eq!(curve.rank(0, &thresholds), 0, "{curve:?}");
+            }
+            assert_eq!(curve.rank(u64::MAX, &thresholds) as usize, tiers, "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn tier_coverage() {
+        let mask = |ranks: &[u8]| ranks.iter().fold(0, |m, &r| m | rank_tier_bit(r));
+        assert!(validate_tier_coverage(0, 5).is_ok());
+        assert!(validate_tier_coverage(mask(&[1, 2, 3]), 3).is_ok());
+        assert!(validate_tier_coverage(mask(&[0, 1, 2, 3]), 3).is_ok());
+        assert!(validate_tier_coverage(mask(&[1, 2, 3]), 4).is_err());
+        assert!(validate_tier_coverage(mask(&[1, 2, 3, 4]), 3).is_ok());
+        assert!(validate_tier_coverage(mask(&[1, 3]), 3).is_err());
+        let all: Vec<u8> = (0..=MAX_RANK_TIERS as u8).collect();
+        assert!(validate_tier_coverage(mask(&all), MAX_RANK_TIERS).is_ok());
+    }
+
+    #[test]
+    fn thresholds_are_inclusive() {
+        let thresholds = [1_000, 5_000];
+        assert_eq!(calculate_rank(999, &thresholds), 0);
+        assert_eq!(calculate_rank(1_000, &thresholds), 1);
+        assert_eq!(calculate_rank(4_999, &thresholds), 1);
+        assert_eq!(calculate_rank(5_000, &thresholds), 2);
+    }
+
+    #[test]
+    fn isqrt_is_floor_sqrt() {
+        for n in samples() {
+            let root = isqrt(n);
+            assert!(root.checked_mul(root).is_some_and(|sq| sq <= n), "{n}");
+            assert!((root + 1).checked_mul(root + 1).is_none_or(|sq| sq > n), "{n}");
+        }
+    }
+
+    #[test]
+    fn validate_rejects_bad_curves() {
+        let bad = [
+            RankCurve::Logarithmic {
+                base_amount: 0,
+                growth: 10,
+                max_rank: 5,
+            },
+            RankCurve::Logarithmic {
+                base_amount: 1,
+                growth: 1,
+                max_rank: 5,
+            },
+            RankCurve::SquareRoot { unit: 0, max_rank: 5 },
+            RankCurve::SquareRoot {
+                unit: 1,
+                max_rank: MAX_RANK_TIERS as u8 + 1,
+            },
+        ];
+        for curve in bad {
+            assert!(curve.validate().is_err(), "{curve:?}");
+        }
+        for (curve, _) in curves() {
+            assert!(curve.validate().is_ok(), "{curve:?}");
+        }
+    }
+}
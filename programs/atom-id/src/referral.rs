@@ -0,0 +1,66 @@
+use anchor_lang::prelude::*;
+
+use crate::{AtomConfig, AtomId, ErrorCode, ReferralCredited};
+
+pub const MAX_BPS: u16 = 10_000;
+
+impl AtomId {
+    /// Adds a burn by an identity this one referred to its referral totals.
+    pub fn credit_referral(&mut self, referred: Pubkey, burn_amount: u64) -> Result<()> {
+        self.referred_burn_volume = self
+            .referred_burn_volume
+            .checked_add(burn_amount)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(ReferralCredited {
+            referrer: self.owner,
+            referred,
+            burn_amount,
+            referred_burn_volume: self.referred_burn_volume,
+        });
+
+        Ok(())
+    }
+}
+
+impl AtomConfig {
+    /// Part of a reward owed to a referred identity that distributors should
+    /// pay to its `referrer` instead.
+    pub fn referral_share(&self, reward: u64) -> u64 {
+        (reward as u128 * self.referral_reward_bps as u128 / MAX_BPS as u128) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credit_referral_adds_up_burns() {
+        let mut referrer = AtomId::deserialize(&mut &[0; AtomId::INIT_SPACE][..]).unwrap();
+        let referred = Pubkey::new_unique();
+
+        referrer.credit_referral(referred, 1_000).unwrap();
+        referrer.credit_referral(referred, 250).unwrap();
+        assert_eq!(referrer.referred_burn_volume, 1_250);
+
+        assert_eq!(
+            referrer.credit_referral(referred, u64::MAX).unwrap_err(),
+            ErrorCode::Overflow.into()
+        );
+        assert_eq!(referrer.referred_burn_volume, 1_250);
+    }
+
+    #[test]
+    fn referral_share_rounds_down() {
+        let mut config = AtomConfig::deserialize(&mut &[0; AtomConfig::INIT_SPACE][..]).unwrap();
+        assert_eq!(config.referral_share(1_000), 0);
+
+        config.referral_reward_bps = 250;
+        assert_eq!(config.referral_share(1_000), 25);
+        assert_eq!(config.referral_share(39), 0);
+
+        config.referral_reward_bps = MAX_BPS;
+        assert_eq!(config.referral_share(u64::MAX), u64::MAX);
+    }
+}
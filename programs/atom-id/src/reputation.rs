@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+
+use crate::{AtomId, ReputationUpdated};
+
+/// History entries folded into `AtomId.reputation_hash`. Verifiers replay
+/// them from `ReputationUpdated` events, starting from the all-zero hash.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ReputationEvent {
+    Created {
+        total_burned: u64,
+        rank: u8,
+        metadata_hash: [u8; 32],
+        slot: u64,
+    },
+    Upgraded {
+        burn_amount: u64,
+        total_burned: u64,
+        rank: u8,
+        metadata_hash: [u8; 32],
+        slot: u64,
+    },
+    MetadataUpdated {
+        metadata_hash: [u8; 32],
+        slot: u64,
+    },
+    Endorsed {
+        endorser: Pubkey,
+        weight: u64,
+        slot: u64,
+    },
+    EndorsementRevoked {
+        endorser: Pubkey,
+        weight: u64,
+        slot: u64,
+    },
+}
+
+impl AtomId {
+    /// Sets `reputation_hash = hash(reputation_hash || event)` and emits the
+    /// event so the chain can be replayed.
+    pub fn record_reputation(&mut self, event: ReputationEvent) -> Result<()> {
+        let data = event.try_to_vec()?;
+        self.reputation_hash = hashv(&[&self.reputation_hash, &data]).to_bytes();
+
+        emit!(ReputationUpdated {
+            owner: self.owner,
+            event,
+            reputation_hash: self.reputation_hash,
+        });
+
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program;
+use crate::sas_schema::{sas_schema, SasSchema};
 use solana_attestation_service_client::instructions::{
     CreateAttestation, CreateAttestationInstructionArgs, CloseAttestation,
     CreateCredential, CreateCredentialInstructionArgs,
@@ -8,6 +9,60 @@ use solana_attestation_service_client::instructions::{
 
 pub const SAS_PROGRAM_ID: Pubkey = solana_program::pubkey!("22zoJMtdu4tQc2PzL74ZUT7FrwgB1Udec8DdW4yw4BdG");
 
+pub const SAS_CREDENTIAL_DISCRIMINATOR: u8 = 0;
+pub const SAS_SCHEMA_DISCRIMINATOR: u8 = 1;
+
+/// Reads the 32-byte key that follows the discriminator of a SAS account,
+/// checking the account is owned by the SAS program and has the expected type.
+fn sas_account_head(account: &AccountInfo, discriminator: u8) -> Result<Pubkey> {
+    require_keys_eq!(*account.owner, SAS_PROGRAM_ID, crate::ErrorCode::InvalidSasProgram);
+    let data = account.try_borrow_data()?;
+    require!(
+        data.len() >= 33 && data[0] == discriminator,
+        crate::ErrorCode::InvalidSasAccountData
+    );
+    Ok(Pubkey::new_from_array(data[1..33].try_into().unwrap()))
+}
+
+/// Authority a SAS credential was created by.
+pub fn sas_credential_authority(account: &AccountInfo) -> Result<Pubkey> {
+    sas_account_head(account, SAS_CREDENTIAL_DISCRIMINATOR)
+}
+
+/// Credential a SAS schema was registered under.
+pub fn sas_schema_credential(account: &AccountInfo) -> Result<Pubkey> {
+    sas_account_head(account, SAS_SCHEMA_DISCRIMINATOR)
+}
+
+/// Layout bytes stored in a SAS schema account, after its credential, name
+/// and description.
+pub fn sas_schema_layout(account: &AccountInfo) -> Result<Vec<u8>> {
+    sas_schema_credential(account)?;
+    let data = account.try_borrow_data()?;
+
+    let mut offset = 33;
+    let mut read_vec = |data: &[u8]| -> Result<Vec<u8>> {
+        let len_bytes = data
+            .get(offset..offset + 4)
+            .ok_or(crate::ErrorCode::InvalidSasAccountData)?;
+        let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+        let value = data
+            .get(offset + 4..offset + 4 + len)
+            .ok_or(crate::ErrorCode::InvalidSasAccountData)?;
+        offset += 4 + len;
+        Ok(value.to_vec())
+    };
+
+    let _name = read_vec(&data)?;
+    let _description = read_vec(&data)?;
+    read_vec(&data)
+}
+
+/// Whether an attestation account is live, i.e. not yet closed.
+pub fn attestation_exists(account: &AccountInfo) -> bool {
+    *account.owner == SAS_PROGRAM_ID && !account.data_is_empty()
+}
+
 pub fn derive_attestation_pda(
     credential: &Pubkey,
     schema: &Pubkey,
@@ -24,14 +79,188 @@ pub fn derive_attestation_pda(
     )
 }
 
-pub fn serialize_atomid_attestation_data(rank: u8, total_burned: u64, created_at_slot: u64) -> Vec<u8> {
-    let mut data = Vec::new();
-    data.push(rank);
-    data.extend_from_slice(&total_burned.to_le_bytes());
-    data.extend_from_slice(&created_at_slot.to_le_bytes());
-    data
+/// Attestation payload layout written for new schemas. Version 1 is the
+/// original untagged layout; every later version leads with its version byte.
+pub const ATTESTATION_SCHEMA_VERSION: u8 = 3;
+
+pub const ATTESTATION_VALIDITY_SECONDS: i64 = 365 * 24 * 60 * 60;
+
+sas_schema! {
+    /// Original untagged payload.
+    pub struct AtomIdAttestationV1 {
+        pub rank: u8,
+        pub total_burned: u64,
+        pub created_at_slot: u64,
+    }
+}
+
+sas_schema! {
+    pub struct AtomIdAttestationV2 {
+        pub version: u8,
+        pub rank: u8,
+        pub total_burned: u64,
+        pub created_at_slot: u64,
+    }
+}
+
+sas_schema! {
+    pub struct AtomIdAttestationV3 {
+        pub version: u8,
+        pub rank: u8,
+        pub total_burned: u64,
+        pub created_at_slot: u64,
+        pub updated_at_slot: u64,
+        pub metadata_hash: [u8; 32],
+        pub burn_count: u64,
+    }
+}
+
+sas_schema! {
+    /// Guild payload, under its own schema so guild attestations can't be
+    /// mistaken for identities.
+    pub struct GuildAttestationV1 {
+        pub version: u8,
+        pub rank: u8,
+        pub total_burned: u64,
+        pub burn_count: u64,
+        pub member_count: u32,
+        pub created_at_slot: u64,
+        pub updated_at_slot: u64,
+        pub name: String,
+    }
+}
+
+/// SAS `layout` and `field_names` for a given payload version, matching what
+/// `serialize_atomid_attestation_data` writes.
+pub fn atomid_schema_layout(version: u8) -> Result<(Vec<u8>, Vec<String>)> {
+    match version {
+        1 => Ok((
+            AtomIdAttestationV1::LAYOUT.to_vec(),
+            AtomIdAttestationV1::field_names(),
+        )),
+        2 => Ok((
+            AtomIdAttestationV2::LAYOUT.to_vec(),
+            AtomIdAttestationV2::field_names(),
+        )),
+        3 => Ok((
+            AtomIdAttestationV3::LAYOUT.to_vec(),
+            AtomIdAttestationV3::field_names(),
+        )),
+        _ => err!(crate::ErrorCode::UnsupportedSchemaVersion),
+    }
+}
+
+/// Highest SAS data type tag (`VecString`).
+pub const MAX_SAS_DATA_TYPE: u8 = 25;
+
+pub const MAX_PARTNER_SCHEMA_FIELDS: usize = 16;
+
+/// Checks a partner-supplied layout before it is written into a schema under
+/// our credential: known SAS types only, one non-empty name per field.
+pub fn validate_partner_schema_layout(layout: &[u8], field_names: &[String]) -> Result<()> {
+    require!(
+        !layout.is_empty()
+            && layout.len() <= MAX_PARTNER_SCHEMA_FIELDS
+            && layout.len() == field_names.len(),
+        crate::ErrorCode::InvalidSchemaLayout
+    );
+
+    require!(
+        layout.iter().all(|kind| *kind <= MAX_SAS_DATA_TYPE),
+        crate::ErrorCode::InvalidSchemaLayout
+    );
+
+    require!(
+        field_names
+            .iter()
+            .all(|name| !name.is_empty() && name.len() <= 32),
+        crate::ErrorCode::InvalidSchemaLayout
+    );
+
+    Ok(())
+}
+
+/// SAS `layout` and `field_names` of the guild schema.
+pub fn guild_schema_layout() -> (Vec<u8>, Vec<String>) {
+    (
+        GuildAttestationV1::LAYOUT.to_vec(),
+        GuildAttestationV1::field_names(),
+    )
+}
+
+pub fn metadata_hash(metadata: &str) -> [u8; 32] {
+    solana_program::hash::hash(metadata.as_bytes()).to_bytes()
+}
+
+/// Values an identity attestation payload is built from.
+pub struct AttestationPayload<'a> {
+    pub rank: u8,
+    pub total_burned: u64,
+    pub created_at_slot: u64,
+    pub updated_at_slot: u64,
+    pub metadata: &'a str,
+    pub burn_count: u64,
+}
+
+pub fn serialize_atomid_attestation_data(version: u8, atom_id: &crate::AtomId) -> Result<Vec<u8>> {
+    serialize_attestation_payload(
+        version,
+        &AttestationPayload {
+            rank: atom_id.rank,
+            total_burned: atom_id.total_burned,
+            created_at_slot: atom_id.created_at_slot,
+            updated_at_slot: atom_id.updated_at_slot,
+            metadata: &atom_id.metadata,
+            burn_count: atom_id.burn_count,
+        },
+    )
+}
+
+pub fn serialize_guild_attestation_data(guild: &crate::Guild) -> Vec<u8> {
+    GuildAttestationV1 {
+        version: 1,
+        rank: guild.rank,
+        total_burned: guild.total_burned,
+        burn_count: guild.burn_count,
+        member_count: guild.member_count,
+        created_at_slot: guild.created_at_slot,
+        updated_at_slot: guild.updated_at_slot,
+        name: guild.name.clone(),
+    }
+    .serialize()
+}
+
+pub fn serialize_attestation_payload(version: u8, payload: &AttestationPayload) -> Result<Vec<u8>> {
+    let data = match version {
+        1 => AtomIdAttestationV1 {
+            rank: payload.rank,
+            total_burned: payload.total_burned,
+            created_at_slot: payload.created_at_slot,
+        }
+        .serialize(),
+        2 => AtomIdAttestationV2 {
+            version,
+            rank: payload.rank,
+            total_burned: payload.total_burned,
+            created_at_slot: payload.created_at_slot,
+        }
+        .serialize(),
+        3 => AtomIdAttestationV3 {
+            version,
+            rank: payload.rank,
+            total_burned: payload.total_burned,
+            created_at_slot: payload.created_at_slot,
+            updated_at_slot: payload.updated_at_slot,
+            metadata_hash: metadata_hash(payload.metadata),
+            burn_count: payload.burn_count,
+        }
+        .serialize(),
+        _ => return err!(crate::ErrorCode::UnsupportedSchemaVersion),
+    };
+    Ok(data)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_attestation_instruction(
     payer: Pubkey,
     authority: Pubkey,
@@ -106,6 +335,7 @@ pub fn create_credential_instruction(
     Ok(instruction_struct.instruction(args))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_schema_instruction(
     payer: Pubkey,
     authority: Pubkey,
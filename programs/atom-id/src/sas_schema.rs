@@ -0,0 +1,119 @@
+/// A value that can appear as a field of a SAS attestation payload.
+pub trait SasField {
+    /// SAS schema data type tag written into the schema `layout`.
+    const KIND: u8;
+
+    fn write(&self, out: &mut Vec<u8>);
+}
+
+impl SasField for u8 {
+    const KIND: u8 = 0;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+
+impl SasField for u16 {
+    const KIND: u8 = 1;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl SasField for u32 {
+    const KIND: u8 = 2;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl SasField for u64 {
+    const KIND: u8 = 3;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl SasField for i64 {
+    const KIND: u8 = 8;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl SasField for bool {
+    const KIND: u8 = 10;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl SasField for String {
+    const KIND: u8 = 12;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+/// Fixed-size byte arrays (hashes) are stored as SAS `VecU8`.
+impl<const N: usize> SasField for [u8; N] {
+    const KIND: u8 = 13;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(N as u32).to_le_bytes());
+        out.extend_from_slice(self);
+    }
+}
+
+/// An attestation payload whose SAS layout, field names and serializer all
+/// come from one struct definition. Implement it with `sas_schema!`.
+pub trait SasSchema {
+    const LAYOUT: &'static [u8];
+    const FIELD_NAMES: &'static [&'static str];
+
+    fn write(&self, out: &mut Vec<u8>);
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write(&mut out);
+        out
+    }
+
+    fn field_names() -> Vec<String> {
+        Self::FIELD_NAMES.iter().map(|name| name.to_string()).collect()
+    }
+}
+
+/// Declares a payload struct and derives its `SasSchema` impl, in field order.
+macro_rules! sas_schema {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident {
+            $(pub $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        pub struct $name {
+            $(pub $field: $ty),*
+        }
+
+        impl $crate::sas_schema::SasSchema for $name {
+            const LAYOUT: &'static [u8] = &[$(<$ty as $crate::sas_schema::SasField>::KIND),*];
+            const FIELD_NAMES: &'static [&'static str] = &[$(stringify!($field)),*];
+
+            fn write(&self, out: &mut Vec<u8>) {
+                $($crate::sas_schema::SasField::write(&self.$field, out);)*
+            }
+        }
+    };
+}
+
+pub(crate) use sas_schema;
@@ -0,0 +1,425 @@
+//! Instructions that CPI into, or read accounts of, the Solana Attestation
+//! Service must refuse a spoofed SAS program and SAS accounts owned by any
+//! other program.
+//!
+//! Runs against the built program, so the tests are ignored by default:
+//! `anchor build`, then `cargo test -- --ignored`.
+
+use anchor_lang::{AccountSerialize, InstructionData, ToAccountMetas};
+use atom_id::ErrorCode;
+use litesvm::LiteSVM;
+use solana_sdk::{
+    account::Account,
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program,
+    transaction::{Transaction, TransactionError},
+};
+
+const SAS_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("22zoJMtdu4tQc2PzL74ZUT7FrwgB1Udec8DdW4yw4BdG");
+
+const SAS_CREDENTIAL_DISCRIMINATOR: u8 = 0;
+const SAS_SCHEMA_DISCRIMINATOR: u8 = 1;
+
+struct Env {
+    svm: LiteSVM,
+    admin: Keypair,
+    credential: Pubkey,
+    schema: Pubkey,
+}
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &atom_id::ID).0
+}
+
+fn config_pda() -> Pubkey {
+    pda(&[b"atomid_config"])
+}
+
+fn sas_authority_pda() -> Pubkey {
+    pda(&[b"sas_authority"])
+}
+
+/// Puts an account shaped like a SAS credential or schema at `key`, owned by
+/// `owner`. The 32 bytes after the discriminator are the credential's
+/// authority or the schema's credential.
+fn set_sas_account(svm: &mut LiteSVM, key: Pubkey, owner: Pubkey, discriminator: u8, head: Pubkey) {
+    let mut data = vec![discriminator];
+    data.extend_from_slice(head.as_ref());
+    data.resize(128, 0);
+    svm.set_account(
+        key,
+        Account {
+            lamports: svm.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        },
+    )
+    .unwrap();
+}
+
+fn send(svm: &mut LiteSVM, ix: Instruction, signers: &[&Keypair]) -> Result<(), TransactionError> {
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&signers[0].pubkey()),
+        signers,
+        svm.latest_blockhash(),
+    );
+    svm.send_transaction(tx).map(|_| ()).map_err(|failed| failed.err)
+}
+
+fn custom(code: ErrorCode) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(code.into()))
+}
+
+/// Config pointing at a SAS credential and schema that really are owned by
+/// the SAS program.
+fn setup() -> Env {
+    let mut svm = LiteSVM::new();
+    svm.add_program_from_file(atom_id::ID, "../../target/deploy/atom_id.so")
+        .expect("run `anchor build` first");
+
+    let admin = Keypair::new();
+    svm.airdrop(&admin.pubkey(), 10_000_000_000).unwrap();
+
+    let credential = Pubkey::new_unique();
+    let schema = Pubkey::new_unique();
+    set_sas_account(&mut svm, credential, SAS_PROGRAM_ID, SAS_CREDENTIAL_DISCRIMINATOR, sas_authority_pda());
+    set_sas_account(&mut svm, schema, SAS_PROGRAM_ID, SAS_SCHEMA_DISCRIMINATOR, credential);
+
+    let ix = Instruction {
+        program_id: atom_id::ID,
+        accounts: atom_id::accounts::Initialize {
+            atom_config: config_pda(),
+            admin: admin.pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: atom_id::instruction::Initialize {
+            min_create_burn: 1,
+            rank_thresholds: vec![100, 1_000],
+            burn_mint: Pubkey::new_unique(),
+            sas_credential: credential,
+            sas_schema: schema,
+            sas_authority: sas_authority_pda(),
+        }
+        .data(),
+    };
+    send(&mut svm, ix, &[&admin]).unwrap();
+
+    Env {
+        svm,
+        admin,
+        credential,
+        schema,
+    }
+}
+
+fn initialize_sas_credential_ix(env: &Env, sas_program: Pubkey) -> Instruction {
+    Instruction {
+        program_id: atom_id::ID,
+        accounts: atom_id::accounts::InitializeSasCredential {
+            payer: env.admin.pubkey(),
+            sas_authority: sas_authority_pda(),
+            sas_credential: Pubkey::new_unique(),
+            sas_program,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: atom_id::instruction::InitializeSasCredential {
+            name: "AtomID".to_string(),
+            description: "AtomID credential".to_string(),
+        }
+        .data(),
+    }
+}
+
+fn initialize_sas_schema_ix(env: &Env, credential: Pubkey, sas_program: Pubkey) -> Instruction {
+    Instruction {
+        program_id: atom_id::ID,
+        accounts: atom_id::accounts::InitializeSasSchema {
+            payer: env.admin.pubkey(),
+            sas_authority: sas_authority_pda(),
+            sas_credential: credential,
+            sas_schema: Pubkey::new_unique(),
+            system_program: system_program::ID,
+            sas_program,
+        }
+        .to_account_metas(None),
+        data: atom_id::instruction::InitializeSasSchema {
+            name: "AtomID".to_string(),
+            description: "AtomID identity".to_string(),
+        }
+        .data(),
+    }
+}
+
+fn create_atomid_ix(
+    user: &Keypair,
+    credential: Pubkey,
+    schema: Pubkey,
+    sas_program: Pubkey,
+) -> Instruction {
+    let user_key = user.pubkey();
+    Instruction {
+        program_id: atom_id::ID,
+        accounts: atom_id::accounts::CreateAtomId {
+            atom_id: pda(&[b"atomid", user_key.as_ref()]),
+            atom_config: config_pda(),
+            user: user_key,
+            wallet_link: pda(&[b"wallet_link", user_key.as_ref()]),
+            compressed_atomid: pda(&[b"compressed_atomid", user_key.as_ref()]),
+            linked_attestation: pda(&[b"linked_attestation", user_key.as_ref()]),
+            user_token_account: Pubkey::new_unique(),
+            atom_mint: Pubkey::new_unique(),
+            sas_attestation: Pubkey::new_unique(),
+            sas_credential: credential,
+            sas_schema: schema,
+            sas_authority: sas_authority_pda(),
+            sas_program,
+            token_program: anchor_spl::token::ID,
+            system_program: system_program::ID,
+            referrer_atom_id: None,
+            rank_hooks: None,
+            hook_authority: None,
+        }
+        .to_account_metas(None),
+        data: atom_id::instruction::CreateAtomid {
+            burn_amount: 1,
+            metadata: None,
+            referrer: None,
+        }
+        .data(),
+    }
+}
+
+/// Stores the `PartnerSchema` that `create_partner_schema` would leave
+/// behind, since no SAS program is loaded to create the schema itself.
+fn set_partner_schema(svm: &mut LiteSVM, schema: Pubkey, issuer: Pubkey) {
+    let (key, bump) = Pubkey::find_program_address(&[b"partner_schema", schema.as_ref()], &atom_id::ID);
+    let mut data = Vec::new();
+    atom_id::PartnerSchema { schema, issuer, bump }
+        .try_serialize(&mut data)
+        .unwrap();
+    svm.set_account(
+        key,
+        Account {
+            lamports: svm.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner: atom_id::ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    )
+    .unwrap();
+}
+
+fn create_partner_schema_ix(
+    env: &Env,
+    sas_manager: Pubkey,
+    layout: Vec<u8>,
+    field_names: Vec<String>,
+) -> Instruction {
+    let schema = Pubkey::new_unique();
+    Instruction {
+        program_id: atom_id::ID,
+        accounts: atom_id::accounts::CreatePartnerSchema {
+            partner_schema: pda(&[b"partner_schema", schema.as_ref()]),
+            issuer: Pubkey::new_unique(),
+            atom_config: config_pda(),
+            sas_manager,
+            sas_authority: sas_authority_pda(),
+            sas_credential: env.credential,
+            sas_schema: schema,
+            system_program: system_program::ID,
+            sas_program: SAS_PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: atom_id::instruction::CreatePartnerSchema {
+            name: "partner".to_string(),
+            description: "Partner claims".to_string(),
+            layout,
+            field_names,
+        }
+        .data(),
+    }
+}
+
+fn register_partner_issuer_ix(env: &Env, schema: Pubkey, issuer: Pubkey) -> Instruction {
+    Instruction {
+        program_id: atom_id::ID,
+        accounts: atom_id::accounts::RegisterPartnerIssuer {
+            partner_issuer: pda(&[b"partner_issuer", issuer.as_ref()]),
+            issuer,
+            sas_schema: schema,
+            partner_schema: pda(&[b"partner_schema", schema.as_ref()]),
+            atom_config: config_pda(),
+            sas_manager: env.admin.pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: atom_id::instruction::RegisterPartnerIssuer {
+            name: "partner".to_string(),
+        }
+        .data(),
+    }
+}
+
+/// A program at another address that would happily "succeed" any CPI.
+fn spoofed_program() -> Pubkey {
+    Pubkey::new_unique()
+}
+
+fn funded_user(env: &mut Env) -> Keypair {
+    let user = Keypair::new();
+    env.svm.airdrop(&user.pubkey(), 1_000_000_000).unwrap();
+    user
+}
+
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn initialize_sas_credential_rejects_spoofed_program() {
+    let mut env = setup();
+    let ix = initialize_sas_credential_ix(&env, spoofed_program());
+    let err = send(&mut env.svm, ix, &[&env.admin]).unwrap_err();
+    assert_eq!(err, custom(ErrorCode::InvalidSasProgram));
+}
+
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn initialize_sas_schema_rejects_spoofed_program() {
+    let mut env = setup();
+    let ix = initialize_sas_schema_ix(&env, env.credential, spoofed_program());
+    let err = send(&mut env.svm, ix, &[&env.admin]).unwrap_err();
+    assert_eq!(err, custom(ErrorCode::InvalidSasProgram));
+}
+
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn initialize_sas_schema_rejects_foreign_credential() {
+    let mut env = setup();
+    let credential = Pubkey::new_unique();
+    set_sas_account(&mut env.svm, credential, spoofed_program(), SAS_CREDENTIAL_DISCRIMINATOR, sas_authority_pda());
+
+    let ix = initialize_sas_schema_ix(&env, credential, SAS_PROGRAM_ID);
+    let err = send(&mut env.svm, ix, &[&env.admin]).unwrap_err();
+    assert_eq!(err, custom(ErrorCode::InvalidSasProgram));
+}
+
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn create_atomid_rejects_spoofed_program() {
+    let mut env = setup();
+    let user = funded_user(&mut env);
+    let ix = create_atomid_ix(&user, env.credential, env.schema, spoofed_program());
+    let err = send(&mut env.svm, ix, &[&user]).unwrap_err();
+    assert_eq!(err, custom(ErrorCode::InvalidSasProgram));
+}
+
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn create_atomid_rejects_foreign_credential() {
+    let mut env = setup();
+    let user = funded_user(&mut env);
+    let credential = Pubkey::new_unique();
+    set_sas_account(&mut env.svm, credential, system_program::ID, SAS_CREDENTIAL_DISCRIMINATOR, sas_authority_pda());
+
+    let ix = create_atomid_ix(&user, credential, env.schema, SAS_PROGRAM_ID);
+    let err = send(&mut env.svm, ix, &[&user]).unwrap_err();
+    assert_eq!(err, custom(ErrorCode::InvalidSasProgram));
+}
+
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn create_atomid_rejects_foreign_schema() {
+    let mut env = setup();
+    let user = funded_user(&mut env);
+    let schema = Pubkey::new_unique();
+    set_sas_account(&mut env.svm, schema, spoofed_program(), SAS_SCHEMA_DISCRIMINATOR, env.credential);
+
+    let ix = create_atomid_ix(&user, env.credential, schema, SAS_PROGRAM_ID);
+    let err = send(&mut env.svm, ix, &[&user]).unwrap_err();
+    assert_eq!(err, custom(ErrorCode::InvalidSasProgram));
+}
+
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn register_partner_issuer_rejects_foreign_schema() {
+    let mut env = setup();
+    let schema = Pubkey::new_unique();
+    set_sas_account(&mut env.svm, schema, spoofed_program(), SAS_SCHEMA_DISCRIMINATOR, env.credential);
+
+    let issuer = Pubkey::new_unique();
+    set_partner_schema(&mut env.svm, schema, issuer);
+
+    let ix = register_partner_issuer_ix(&env, schema, issuer);
+    let err = send(&mut env.svm, ix, &[&env.admin]).unwrap_err();
+    assert_eq!(err, custom(ErrorCode::InvalidSasProgram));
+}
+
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn register_partner_issuer_rejects_schema_of_another_issuer() {
+    let mut env = setup();
+    let schema = Pubkey::new_unique();
+    set_sas_account(&mut env.svm, schema, SAS_PROGRAM_ID, SAS_SCHEMA_DISCRIMINATOR, env.credential);
+    set_partner_schema(&mut env.svm, schema, Pubkey::new_unique());
+
+    let ix = register_partner_issuer_ix(&env, schema, Pubkey::new_unique());
+    let err = send(&mut env.svm, ix, &[&env.admin]).unwrap_err();
+    assert_eq!(err, custom(ErrorCode::InvalidSasSchema));
+}
+
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn create_partner_schema_requires_sas_manager() {
+    let mut env = setup();
+    let outsider = funded_user(&mut env);
+    let ix = create_partner_schema_ix(&env, outsider.pubkey(), vec![0], vec!["score".to_string()]);
+    let err = send(&mut env.svm, ix, &[&outsider]).unwrap_err();
+    assert_eq!(err, custom(ErrorCode::Unauthorized));
+}
+
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn create_partner_schema_rejects_invalid_layouts() {
+    let mut env = setup();
+    let invalid = [
+        (vec![], vec![]),
+        (vec![0, 3], vec!["score".to_string()]),
+        (vec![26], vec!["score".to_string()]),
+        (vec![0], vec![String::new()]),
+    ];
+
+    for (layout, field_names) in invalid {
+        let ix = create_partner_schema_ix(&env, env.admin.pubkey(), layout, field_names);
+        let err = send(&mut env.svm, ix, &[&env.admin]).unwrap_err();
+        assert_eq!(err, custom(ErrorCode::InvalidSchemaLayout));
+    }
+}
+
+/// The same accounts with the real SAS program get past every ownership
+/// check; they only fail later, since no SAS program is loaded here.
+#[test]
+#[ignore = "needs target/deploy/atom_id.so from `anchor build`"]
+fn genuine_sas_accounts_pass_ownership_checks() {
+    let mut env = setup();
+    let ix = initialize_sas_schema_ix(&env, env.credential, SAS_PROGRAM_ID);
+    let err = send(&mut env.svm, ix, &[&env.admin]).unwrap_err();
+    assert_ne!(err, custom(ErrorCode::InvalidSasProgram));
+
+    let schema = Pubkey::new_unique();
+    set_sas_account(&mut env.svm, schema, SAS_PROGRAM_ID, SAS_SCHEMA_DISCRIMINATOR, env.credential);
+    let issuer = Pubkey::new_unique();
+    set_partner_schema(&mut env.svm, schema, issuer);
+    let ix = register_partner_issuer_ix(&env, schema, issuer);
+    send(&mut env.svm, ix, &[&env.admin]).unwrap();
+
+    let ix = create_partner_schema_ix(&env, env.admin.pubkey(), vec![0, 12], vec!["tier".to_string(), "note".to_string()]);
+    let err = send(&mut env.svm, ix, &[&env.admin]).unwrap_err();
+    assert_ne!(err, custom(ErrorCode::InvalidSchemaLayout));
+}
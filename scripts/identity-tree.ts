@@ -0,0 +1,89 @@
+import { PublicKey } from "@solana/web3.js";
+import { createHash } from "crypto";
+
+// Off-chain mirror of the program's identity tree. Replay
+// CompressedAtomIdUpdated / CompressedAtomIdPromoted events into it to get
+// the roots and proofs that upgrade and promote instructions expect.
+
+export interface CompressedAtomId {
+  owner: PublicKey;
+  totalBurned: bigint;
+  rank: number;
+  createdAtSlot: bigint;
+  updatedAtSlot: bigint;
+  burnCount: bigint;
+}
+
+const EMPTY_LEAF = Buffer.alloc(32);
+
+function sha256(...parts: Buffer[]): Buffer {
+  const hash = createHash("sha256");
+  parts.forEach((part) => hash.update(part));
+  return hash.digest();
+}
+
+function u64(value: bigint): Buffer {
+  const buf = Buffer.alloc(8);
+  buf.writeBigUInt64LE(value);
+  return buf;
+}
+
+// Borsh layout of the Rust struct, in field order
+export function hashCompressedAtomId(leaf: CompressedAtomId): Buffer {
+  const data = Buffer.concat([
+    leaf.owner.toBuffer(),
+    u64(leaf.totalBurned),
+    Buffer.from([leaf.rank]),
+    u64(leaf.createdAtSlot),
+    u64(leaf.updatedAtSlot),
+    u64(leaf.burnCount),
+  ]);
+  return sha256(Buffer.from("atomid_leaf"), data);
+}
+
+export class LocalIdentityTree {
+  private leaves: Buffer[] = [];
+  private zeros: Buffer[] = [];
+
+  constructor(public readonly depth: number) {
+    let node = EMPTY_LEAF;
+    for (let i = 0; i < depth; i++) {
+      this.zeros.push(node);
+      node = sha256(node, node);
+    }
+  }
+
+  append(leaf: Buffer): number {
+    this.leaves.push(leaf);
+    return this.leaves.length - 1;
+  }
+
+  set(index: number, leaf: Buffer) {
+    this.leaves[index] = leaf;
+  }
+
+  private level(height: number): Buffer[] {
+    let nodes = this.leaves;
+    for (let h = 0; h < height; h++) {
+      const next: Buffer[] = [];
+      for (let i = 0; i < nodes.length; i += 2) {
+        next.push(sha256(nodes[i], nodes[i + 1] ?? this.zeros[h]));
+      }
+      nodes = next;
+    }
+    return nodes;
+  }
+
+  root(): Buffer {
+    return this.level(this.depth)[0] ?? sha256(this.zeros[this.depth - 1], this.zeros[this.depth - 1]);
+  }
+
+  proof(index: number): Buffer[] {
+    const proof: Buffer[] = [];
+    for (let h = 0; h < this.depth; h++) {
+      const sibling = (index >> h) ^ 1;
+      proof.push(this.level(h)[sibling] ?? this.zeros[h]);
+    }
+    return proof;
+  }
+}
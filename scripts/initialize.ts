@@ -65,7 +65,7 @@ async function main() {
     console.log("Current min burn:", configAccount.minCreateBurn.toString());
     console.log("Current burn mint:", configAccount.burnMint.toString());
     console.log(
-      "\n💡 If you need to update config, use admin_update_config instruction."
+      "\n💡 If you need to update config, queue_config_change and execute it after the timelock."
     );
     return;
   } catch (err) {
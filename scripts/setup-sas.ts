@@ -135,20 +135,11 @@ async function main() {
     console.log("Creating schema through AtomID program...");
 
     const schemaDescription = "AtomID rank attestation - Proof of ATOM burned and trust level";
-    const layout = Buffer.from([0, 3, 3]); // [U8, U64, U64]
-    const fieldNames = ["rank", "total_burned", "created_at_slot"];
-
-    console.log("Layout:", layout, "(U8, U64, U64)");
-    console.log("Fields:", fieldNames);
-
+    // Layout and field names are derived on-chain from the program's
+    // current attestation payload version.
     try {
       const tx = await program.methods
-        .initializeSasSchema(
-          schemaName,
-          schemaDescription,
-          layout,
-          fieldNames
-        )
+        .initializeSasSchema(schemaName, schemaDescription)
         .accounts({
           payer: keypair.publicKey,
           sasCredential: credentialPda,
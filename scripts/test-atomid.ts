@@ -228,7 +228,7 @@ async function main() {
 
     try {
       const tx = await program.methods
-        .createAtomid(burnAmount, testMetadata)
+        .createAtomid(burnAmount, testMetadata, null)
         .accountsPartial({
           atomId: pdas.atomIdPda,
           atomConfig: pdas.configPda,
@@ -299,18 +299,18 @@ async function main() {
     console.log("   New Metadata: ", newMetadata);
     console.log("");
 
-    // For upgrade, we need old and new attestation PDAs
-    const oldAttestationPda = pdas.sasAttestationPda;
+    // The attestation is closed and reissued at the same address
+    const sasAttestationPda = pdas.sasAttestationPda;
 
-    // New attestation will use a different derivation (new nonce)
-    // For testing, we'll use a timestamp as nonce
-    const newNonce = new PublicKey(payer.publicKey.toBuffer()); // Same user, but SAS will handle versioning
-    const [newSasAttestationPda] = PublicKey.findProgramAddressSync(
+    // After a schema change the attestation under the previous schema is
+    // closed too
+    const hasPreviousSchema = !config.previousSasSchema.equals(PublicKey.default);
+    const [previousSasAttestationPda] = PublicKey.findProgramAddressSync(
       [
         Buffer.from("attestation"),
-        sasCredential.toBuffer(),
-        sasSchema.toBuffer(),
-        newNonce.toBuffer(),
+        config.previousSasCredential.toBuffer(),
+        config.previousSasSchema.toBuffer(),
+        payer.publicKey.toBuffer(),
       ],
       SAS_PROGRAM_ID
     );
@@ -328,8 +328,7 @@ async function main() {
           user: payer.publicKey,
           userTokenAccount,
           atomMint: burnMint,
-          oldSasAttestation: oldAttestationPda,
-          newSasAttestation: newSasAttestationPda,
+          sasAttestation: sasAttestationPda,
           sasCredential,
           sasSchema,
           sasAuthority: pdas.sasAuthorityPda,
@@ -337,6 +336,8 @@ async function main() {
           sasProgram: SAS_PROGRAM_ID,
           tokenProgram: TOKEN_PROGRAM_ID,
           systemProgram: SystemProgram.programId,
+          previousSasCredential: hasPreviousSchema ? config.previousSasCredential : null,
+          previousSasAttestation: hasPreviousSchema ? previousSasAttestationPda : null,
         })
         .preInstructions([modifyComputeUnits])
         .rpc();
@@ -359,10 +360,10 @@ async function main() {
       console.log("   Updated at:   ", upgradedAtomId.updatedAtSlot.toString());
 
       // Check new attestation created
-      const newAttestationInfo = await connection.getAccountInfo(newSasAttestationPda);
+      const newAttestationInfo = await connection.getAccountInfo(sasAttestationPda);
       if (newAttestationInfo) {
         console.log("✅ New attestation created:");
-        console.log("   Address:", newSasAttestationPda.toString());
+        console.log("   Address:", sasAttestationPda.toString());
       } else {
         console.log("⚠️  New attestation not found (unexpected)");
       }
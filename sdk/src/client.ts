@@ -34,6 +34,33 @@ export class AtomIDClient {
     );
   }
 
+  private findWalletLinkPDA(wallet: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("wallet_link"), wallet.toBuffer()],
+      this.programId
+    );
+  }
+
+  /**
+   * Primary wallet of the identity `wallet` belongs to: the wallet itself if
+   * it holds an AtomID, the owner it was linked to with `link_wallet`, or
+   * null if neither.
+   */
+  async resolveOwner(wallet: PublicKey | string): Promise<PublicKey | null> {
+    const walletPubkey = typeof wallet === "string" ? new PublicKey(wallet) : wallet;
+
+    const [pda] = this.findAtomIDPDA(walletPubkey);
+    const [linkPda] = this.findWalletLinkPDA(walletPubkey);
+    const [own, link] = await this.connection.getMultipleAccountsInfo([pda, linkPda]);
+
+    if (own) {
+      return walletPubkey;
+    }
+
+    // WalletLink layout: discriminator, wallet, owner, atom_id, ...
+    return link ? new PublicKey(link.data.slice(40, 72)) : null;
+  }
+
   private parseAtomIDAccount(
     data: Buffer,
     wallet: PublicKey,
@@ -82,7 +109,16 @@ export class AtomIDClient {
         };
       }
 
-      const [pda] = this.findAtomIDPDA(walletPubkey);
+      // Linked wallets resolve to the identity they were linked to
+      const owner = await this.resolveOwner(walletPubkey);
+      if (!owner) {
+        return {
+          exists: false,
+          account: null
+        };
+      }
+
+      const [pda] = this.findAtomIDPDA(owner);
       const accountInfo = await this.connection.getAccountInfo(pda);
 
       if (!accountInfo) {
@@ -94,9 +130,12 @@ export class AtomIDClient {
 
       const account = this.parseAtomIDAccount(
         accountInfo.data,
-        walletPubkey,
+        owner,
         pda
       );
+      if (!owner.equals(walletPubkey)) {
+        account.linkedWallet = walletPubkey;
+      }
 
       this.cache.set(cacheKey, {
         data: account,
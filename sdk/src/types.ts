@@ -9,6 +9,8 @@ export interface AtomIDAccount {
   createdAtSlot: number;
   updatedAtSlot: number;
   pda: PublicKey;
+  /** Wallet that was queried, when it is linked to `wallet`'s identity */
+  linkedWallet?: PublicKey;
 }
 
 export interface VerificationResult {
@@ -0,0 +1,86 @@
+import { PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import { createHash } from "crypto";
+import {
+  CompressedAtomId,
+  hashCompressedAtomId,
+  LocalIdentityTree,
+} from "../scripts/identity-tree";
+
+// Expected values come from the program's own `IdentityTree` and
+// `CompressedAtomId::leaf_hash`, so the mirror must agree with them exactly.
+const EMPTY_ROOT =
+  "536d98837f2dd165a55d5eeae91485954472d56f246df256bf3cae19352a123c";
+const LEAF_HASHES = [
+  "8cebfcc05d7bef92da7e4fe9beafb6868140e2a860a4927f6060fa9adb2865f1",
+  "fdceb0eda4161bed9430f12f9a27db998e472ba8ac7af730cc9cdff08d31cb99",
+  "01787b3b30777913de16f5c46eb291895aaa5c606432269bd796433d3ee7614f",
+];
+const ROOT_AFTER_THREE =
+  "789b9f531e0560ac9328affef7079d5f5782cdcf8ab6c37ff2d8a6607bca501a";
+
+function compressed(burnCount: bigint): CompressedAtomId {
+  return {
+    owner: new PublicKey(Buffer.alloc(32, 1)),
+    totalBurned: BigInt(5_000_000),
+    rank: 2,
+    createdAtSlot: BigInt(10),
+    updatedAtSlot: BigInt(20),
+    burnCount,
+  };
+}
+
+function sha256(...parts: Buffer[]): Buffer {
+  const hash = createHash("sha256");
+  parts.forEach((part) => hash.update(part));
+  return hash.digest();
+}
+
+// Same folding as the program's `compute_path`
+function rootFromProof(leaf: Buffer, index: number, proof: Buffer[]): Buffer {
+  return proof.reduce(
+    (node, sibling, height) =>
+      (index >> height) & 1 ? sha256(sibling, node) : sha256(node, sibling),
+    leaf
+  );
+}
+
+describe("identity-tree", () => {
+  it("hashes leaves like the program", () => {
+    LEAF_HASHES.forEach((expected, i) => {
+      const hash = hashCompressedAtomId(compressed(BigInt(i + 1)));
+      expect(hash.toString("hex")).to.equal(expected);
+    });
+  });
+
+  it("matches the program's roots", () => {
+    const tree = new LocalIdentityTree(4);
+    expect(tree.root().toString("hex")).to.equal(EMPTY_ROOT);
+
+    LEAF_HASHES.forEach((hash, i) => {
+      expect(tree.append(Buffer.from(hash, "hex"))).to.equal(i);
+    });
+    expect(tree.root().toString("hex")).to.equal(ROOT_AFTER_THREE);
+  });
+
+  it("produces proofs that fold to the root", () => {
+    const tree = new LocalIdentityTree(4);
+    const leaves = LEAF_HASHES.map((hash) => Buffer.from(hash, "hex"));
+    leaves.forEach((leaf) => tree.append(leaf));
+
+    leaves.forEach((leaf, index) => {
+      const proof = tree.proof(index);
+      expect(proof).to.have.length(4);
+      expect(rootFromProof(leaf, index, proof).equals(tree.root())).to.be.true;
+    });
+
+    // A promoted leaf is cleared, which the mirror records with `set`
+    tree.set(1, Buffer.alloc(32));
+    expect(
+      rootFromProof(Buffer.alloc(32), 1, tree.proof(1)).equals(tree.root())
+    ).to.be.true;
+    expect(
+      rootFromProof(leaves[2], 2, tree.proof(2)).equals(tree.root())
+    ).to.be.true;
+  });
+});